@@ -0,0 +1,338 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::fetch::{Method, Request, Response};
+use crate::fnv;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// use fresh responses, revalidate stale ones
+    Normal,
+    /// only ever use the cache, never the network
+    Offline,
+    /// always go to the network, then store what comes back
+    Refresh,
+}
+
+/// Responses to GET requests stored on disk, one JSON file per request; see key().
+///
+/// Whether a stored response can be used without asking the server depends on its
+/// Cache-Control max-age; otherwise it's revalidated with If-None-Match or
+/// If-Modified-Since when it has an ETag or Last-Modified.
+///
+/// Responses with none of those could never be used again, so they aren't stored; and
+/// that means `Mode::Offline` can't answer requests for them either.
+pub struct Cache {
+    dir: PathBuf,
+    mode: Mode,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// seconds since the unix epoch
+    stored_at: u64,
+    response: Response,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, mode: Mode) -> Self {
+        Cache { dir, mode }
+    }
+
+    /// `$XDG_CACHE_HOME/graph-do-smell` or `~/.cache/graph-do-smell`, or in the temp
+    /// directory if we've got neither.
+    pub fn default_dir() -> PathBuf {
+        let base = match (std::env::var_os("XDG_CACHE_HOME"), std::env::var_os("HOME")) {
+            (Some(dir), _) if !dir.is_empty() => PathBuf::from(dir),
+            (_, Some(home)) if !home.is_empty() => PathBuf::from(home).join(".cache"),
+            _ => std::env::temp_dir(),
+        };
+        base.join(env!("CARGO_PKG_NAME"))
+    }
+
+    /// Answers the request from the cache if we can, otherwise uses `send` and stores the
+    /// response.
+    pub fn fetch<F>(&self, request: &Request, send: F) -> anyhow::Result<Response>
+    where
+        F: FnOnce(&Request) -> anyhow::Result<Response>,
+    {
+        if request.method != Method::Get {
+            anyhow::ensure!(
+                self.mode != Mode::Offline,
+                "can't {} {} while offline",
+                request.method.as_str(),
+                request.url
+            );
+            return send(request);
+        }
+
+        let started = Instant::now();
+        let path = self.path(request);
+        let entry = match self.mode {
+            Mode::Refresh => None,
+            _ => read_json(&path),
+        };
+
+        if let Some(entry) = &entry {
+            if self.mode == Mode::Offline || entry.is_fresh() {
+                let mut response = entry.response.clone();
+                response.elapsed = started.elapsed();
+                response.from_cache = true;
+                return Ok(response);
+            }
+        }
+
+        anyhow::ensure!(
+            self.mode != Mode::Offline,
+            "{} isn't cached and we're offline",
+            request.url
+        );
+
+        let response = match entry {
+            Some(mut entry) => {
+                let mut conditional = request.clone();
+                conditional.headers.extend(entry.validators());
+
+                let response = send(&conditional)?;
+
+                if response.status == 304 {
+                    entry.revalidated(&response);
+                    entry.response.elapsed = response.elapsed;
+                    entry.response.from_cache = true;
+                    self.store(&path, &entry);
+                    return Ok(entry.response);
+                }
+
+                response
+            }
+            None => send(request)?,
+        };
+
+        if is_reusable(&response) {
+            self.store(
+                &path,
+                &Entry {
+                    stored_at: now(),
+                    response: response.clone(),
+                },
+            );
+        }
+
+        Ok(response)
+    }
+
+    fn path(&self, request: &Request) -> PathBuf {
+        self.dir.join(format!("{}.json", key(request)))
+    }
+
+    /// A cache we can't write to shouldn't fail the query, so this only complains.
+    fn store(&self, path: &Path, entry: &Entry) {
+        if let Err(err) = write_json(&self.dir, path, entry) {
+            eprintln!("failed to cache response in {}: {:#}", path.display(), err);
+        }
+    }
+}
+
+impl Entry {
+    fn is_fresh(&self) -> bool {
+        if cache_control(&self.response).any(|d| d == "no-cache" || d == "no-store") {
+            return false;
+        }
+
+        max_age(&self.response).map_or(false, |max_age| {
+            now() < self.stored_at.saturating_add(max_age)
+        })
+    }
+
+    fn validators(&self) -> Vec<(String, String)> {
+        let mut headers = vec![];
+        if let Some(etag) = self.response.header("etag") {
+            headers.push(("If-None-Match".to_string(), etag.to_string()));
+        }
+        if let Some(modified) = self.response.header("last-modified") {
+            headers.push(("If-Modified-Since".to_string(), modified.to_string()));
+        }
+        headers
+    }
+
+    /// A 304 may come with newer caching headers; those replace the stored ones.
+    fn revalidated(&mut self, not_modified: &Response) {
+        for name in ["cache-control", "expires", "etag", "last-modified", "date"] {
+            let newer = not_modified
+                .headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !newer.is_empty() {
+                let headers = &mut self.response.headers;
+                headers.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+                headers.extend(newer);
+            }
+        }
+        self.stored_at = now();
+    }
+}
+
+/// A 200 that's either fresh for a while or can be revalidated.
+fn is_reusable(response: &Response) -> bool {
+    response.status == 200
+        && !cache_control(response).any(|d| d == "no-store")
+        && (max_age(response).map_or(false, |max_age| max_age > 0)
+            || response.header("etag").is_some()
+            || response.header("last-modified").is_some())
+}
+
+fn max_age(response: &Response) -> Option<u64> {
+    cache_control(response)
+        .filter_map(|d| d.strip_prefix("max-age=")?.trim_matches('"').parse().ok())
+        .last()
+}
+
+/// Lower cased Cache-Control directives.
+fn cache_control(response: &Response) -> impl Iterator<Item = String> + '_ {
+    response
+        .headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("cache-control"))
+        .flat_map(|(_, v)| v.split(','))
+        .map(|d| d.trim().to_ascii_lowercase())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+/// Identifies a request by its method, URL, headers and body.
+///
+/// Recordings are checked in and have to keep working, so this is an fnv::hash and leaves
+/// User-Agent out; it has our version in it.
+pub fn key(request: &Request) -> String {
+    let mut headers = request
+        .headers
+        .iter()
+        .filter(|(k, _)| !k.eq_ignore_ascii_case("user-agent"))
+        .map(|(k, v)| (k.to_ascii_lowercase(), v))
+        .collect::<Vec<_>>();
+    headers.sort();
+
+    let mut parts = vec![request.method.as_str(), request.url.as_str()];
+    for (name, value) in &headers {
+        parts.push(name);
+        parts.push(value);
+    }
+    if let Some(body) = &request.body {
+        parts.push(body);
+    }
+
+    format!("{:016x}", fnv::hash(&parts))
+}
+
+pub fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let file = std::fs::File::open(path).ok()?;
+    serde_json::from_reader(std::io::BufReader::new(file)).ok()
+}
+
+/// Writes `value` to `path` in `dir`, creating `dir` if needed.
+pub fn write_json<T: Serialize>(dir: &Path, path: &Path, value: &T) -> anyhow::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+
+    /* write somewhere else first so readers never see half a file */
+    let tmp = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let json = serde_json::to_vec_pretty(value)?;
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* recordings are named by these, so they must never change */
+    #[test]
+    fn keys_are_stable() {
+        assert_eq!(
+            key(&Request::get("https://example.com/")),
+            "ae9c3922b465bc4b"
+        );
+
+        let mut request = Request::get("https://example.com/search");
+        request.method = Method::Post;
+        request.headers = vec![
+            (
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            ),
+            ("Accept".to_string(), "text/html".to_string()),
+        ];
+        request.body = Some("q=fish".to_string());
+        assert_eq!(key(&request), "0239f028c773129f");
+
+        /* header order and User-Agent don't matter */
+        request.headers.reverse();
+        request
+            .headers
+            .push(("User-Agent".to_string(), "something/2.0".to_string()));
+        assert_eq!(key(&request), "0239f028c773129f");
+    }
+
+    #[test]
+    fn keys_differ() {
+        let mut a = Request::get("https://example.com/");
+        let mut b = a.clone();
+        b.method = Method::Head;
+        assert_ne!(key(&a), key(&b));
+
+        /* where one field ends and the next starts is part of the key */
+        a.headers = vec![("ab".to_string(), "c".to_string())];
+        b = Request::get("https://example.com/");
+        b.headers = vec![("a".to_string(), "bc".to_string())];
+        assert_ne!(key(&a), key(&b));
+    }
+
+    #[test]
+    fn only_reusable_responses_are_stored() {
+        let response = |status, headers: &[(&str, &str)]| Response {
+            status,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(!is_reusable(&response(200, &[])));
+        assert!(!is_reusable(&response(
+            200,
+            &[("cache-control", "max-age=0")]
+        )));
+        assert!(is_reusable(&response(
+            200,
+            &[("cache-control", "public, max-age=60")]
+        )));
+        assert!(is_reusable(&response(200, &[("etag", "\"x\"")])));
+        assert!(is_reusable(&response(
+            200,
+            &[("last-modified", "Mon, 01 Jan 2024 00:00:00 GMT")]
+        )));
+        assert!(!is_reusable(&response(
+            200,
+            &[("cache-control", "no-store"), ("etag", "\"x\"")]
+        )));
+        assert!(!is_reusable(&response(404, &[("etag", "\"x\"")])));
+    }
+}
@@ -0,0 +1,304 @@
+use chrono::{DateTime, FixedOffset, Months, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+
+/// Languages that write 1.299,00 instead of 1,299.00.
+const DECIMAL_COMMA: &[&str] = &[
+    "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu", "id", "is",
+    "it", "lt", "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr",
+    "uk", "vi",
+];
+
+/// The first number in `text`, like "1,299.00" out of "1,299.00 €".
+///
+/// `locale` is a language tag like "de" or "en-US" and decides whether "," or "." is the
+/// decimal point. Without one we guess; the last of "," and "." is the decimal point if
+/// both are there, and a lone "," followed by exactly three digits is a thousands
+/// separator.
+pub fn number(text: &str, locale: Option<&str>) -> Result<f64, String> {
+    let digits = numeric_part(text).ok_or_else(|| format!("no number in {:?}", text))?;
+
+    let decimal = match locale {
+        Some(locale) => decimal_point(locale),
+        None => guess_decimal_point(digits),
+    };
+
+    let mut cleaned = String::with_capacity(digits.len());
+    for c in digits.chars() {
+        match c {
+            '0'..='9' | '-' | '+' => cleaned.push(c),
+            '\u{2212}' => cleaned.push('-'),
+            c if c == decimal => cleaned.push('.'),
+            /* anything else in here is grouping */
+            _ => (),
+        }
+    }
+
+    cleaned
+        .parse()
+        .map_err(|_| format!("can't read a number from {:?}", text))
+}
+
+pub fn integer(text: &str, locale: Option<&str>) -> Result<i64, String> {
+    let n = number(text, locale)?;
+    if n.fract() != 0.0 || n < i64::MIN as f64 || n > i64::MAX as f64 {
+        return Err(format!("{:?} isn't a whole number", text));
+    }
+    Ok(n as i64)
+}
+
+/// The run of text from the first digit (and a sign just before it) up to the last digit
+/// that's only separated from it by digits, separators and spaces.
+fn numeric_part(text: &str) -> Option<&str> {
+    let first = text.find(|c: char| c.is_ascii_digit())?;
+    let start = match text[..first].chars().next_back() {
+        Some(c @ ('-' | '+' | '\u{2212}')) => first - c.len_utf8(),
+        _ => first,
+    };
+
+    let mut end = first;
+    for (i, c) in text[first..].char_indices() {
+        match c {
+            '0'..='9' => end = first + i + 1,
+            '.' | ',' | '\'' | '\u{2019}' | ' ' | '\u{a0}' | '\u{202f}' => (),
+            _ => break,
+        }
+    }
+
+    Some(&text[start..end])
+}
+
+fn decimal_point(locale: &str) -> char {
+    let locale = locale.to_ascii_lowercase().replace('_', "-");
+    let language = locale.split('-').next().unwrap_or_default();
+
+    /* the swiss write 1'299.00 whatever the language */
+    if locale.ends_with("-ch") {
+        return '.';
+    }
+
+    if DECIMAL_COMMA.contains(&language) {
+        ','
+    } else {
+        '.'
+    }
+}
+
+fn guess_decimal_point(digits: &str) -> char {
+    match (digits.rfind('.'), digits.rfind(',')) {
+        (Some(dot), Some(comma)) => {
+            if dot > comma {
+                '.'
+            } else {
+                ','
+            }
+        }
+        (None, Some(comma)) => {
+            let after = &digits[comma + 1..];
+            let grouping = digits.matches(',').count() > 1
+                || (after.len() == 3 && after.chars().all(|c| c.is_ascii_digit()));
+            if grouping {
+                '.'
+            } else {
+                ','
+            }
+        }
+        _ => '.',
+    }
+}
+
+/// Reads yes/no sorts of words.
+pub fn boolean(text: &str) -> Result<bool, String> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "on" | "1" | "checked" | "selected" | "enabled" => Ok(true),
+        "false" | "no" | "n" | "off" | "0" | "" | "disabled" => Ok(false),
+        _ => Err(format!("can't read a boolean from {:?}", text)),
+    }
+}
+
+/// Reads a date and time, with `format` if it's given (in strftime syntax), otherwise as
+/// RFC 3339, RFC 2822, "2006-01-02 15:04:05", "2006-01-02" or things like "3 days ago".
+/// Times without a zone are taken to be in UTC.
+pub fn datetime(
+    text: &str,
+    format: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<FixedOffset>, String> {
+    let text = text.trim();
+    let utc = |t: NaiveDateTime| Utc.from_utc_datetime(&t).fixed_offset();
+    let midnight = |d: NaiveDate| d.and_hms_opt(0, 0, 0).map(utc);
+
+    if let Some(format) = format {
+        return DateTime::parse_from_str(text, format)
+            .ok()
+            .or_else(|| NaiveDateTime::parse_from_str(text, format).ok().map(utc))
+            .or_else(|| {
+                NaiveDate::parse_from_str(text, format)
+                    .ok()
+                    .and_then(midnight)
+            })
+            .ok_or_else(|| format!("{:?} doesn't match the format {:?}", text, format));
+    }
+
+    DateTime::parse_from_rfc3339(text)
+        .ok()
+        .or_else(|| DateTime::parse_from_rfc2822(text).ok())
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(utc)
+        })
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S")
+                .ok()
+                .map(utc)
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(midnight)
+        })
+        .or_else(|| relative(text, now).map(|t| t.fixed_offset()))
+        .ok_or_else(|| format!("can't read a date from {:?}", text))
+}
+
+/// "now", "today", "yesterday" and "<n> <unit>s ago". None for times too long ago for
+/// chrono.
+fn relative(text: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let text = text.to_ascii_lowercase();
+    let today = now.date_naive().and_hms_opt(0, 0, 0)?.and_utc();
+
+    match text.as_str() {
+        "now" | "just now" => return Some(now),
+        "today" => return Some(today),
+        "yesterday" => return today.checked_sub_signed(TimeDelta::try_days(1)?),
+        _ => (),
+    }
+
+    let mut words = text.strip_suffix(" ago")?.split_whitespace();
+    let n = match words.next()? {
+        "a" | "an" | "one" => 1,
+        n => n.parse::<u32>().ok()?,
+    };
+    let unit = words.next()?;
+    if words.next().is_some() {
+        return None;
+    }
+
+    let ago = match unit.strip_suffix('s').unwrap_or(unit) {
+        "second" | "sec" => TimeDelta::try_seconds(n.into()),
+        "minute" | "min" => TimeDelta::try_minutes(n.into()),
+        "hour" | "hr" => TimeDelta::try_hours(n.into()),
+        "day" => TimeDelta::try_days(n.into()),
+        "week" => TimeDelta::try_weeks(n.into()),
+        "month" => return now.checked_sub_months(Months::new(n)),
+        "year" => return now.checked_sub_months(Months::new(n.checked_mul(12)?)),
+        _ => None,
+    };
+
+    now.checked_sub_signed(ago?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number("1,299.00 €", None), Ok(1299.0));
+        assert_eq!(number("€1.299,00", None), Ok(1299.0));
+        assert_eq!(number("1,5 kg", None), Ok(1.5));
+        assert_eq!(number("1,299", None), Ok(1299.0));
+        assert_eq!(number("1,299,000", None), Ok(1299000.0));
+        assert_eq!(number("1.299", Some("de")), Ok(1299.0));
+        assert_eq!(number("1,299", Some("de-DE")), Ok(1.299));
+        assert_eq!(number("1'299.50", Some("de-CH")), Ok(1299.5));
+        assert_eq!(number("1\u{a0}000,50", Some("fr")), Ok(1000.5));
+        assert_eq!(number("price: -3.5", None), Ok(-3.5));
+        assert_eq!(number("\u{2212}5", None), Ok(-5.0));
+        assert!(number("none", None).is_err());
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(integer("1 000 items", None), Ok(1000));
+        assert_eq!(integer("-42", None), Ok(-42));
+        assert!(integer("2.5", None).is_err());
+        assert!(integer("99999999999999999999", None).is_err());
+    }
+
+    #[test]
+    fn booleans() {
+        assert_eq!(boolean(" Yes "), Ok(true));
+        assert_eq!(boolean("checked"), Ok(true));
+        assert_eq!(boolean("off"), Ok(false));
+        assert_eq!(boolean(""), Ok(false));
+        assert!(boolean("maybe").is_err());
+    }
+
+    fn read(text: &str, format: Option<&str>) -> Result<String, String> {
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
+        datetime(text, format, now).map(|t| t.to_rfc3339())
+    }
+
+    #[test]
+    fn datetimes() {
+        assert_eq!(
+            read("2024-01-02T03:04:05+01:00", None).as_deref(),
+            Ok("2024-01-02T03:04:05+01:00")
+        );
+        assert_eq!(
+            read("Tue, 1 Jul 2003 10:52:37 +0200", None).as_deref(),
+            Ok("2003-07-01T10:52:37+02:00")
+        );
+        assert_eq!(
+            read("2024-01-02 03:04:05", None).as_deref(),
+            Ok("2024-01-02T03:04:05+00:00")
+        );
+        assert_eq!(
+            read(" 2024-01-02 ", None).as_deref(),
+            Ok("2024-01-02T00:00:00+00:00")
+        );
+        assert_eq!(
+            read("05/03/2024", Some("%d/%m/%Y")).as_deref(),
+            Ok("2024-03-05T00:00:00+00:00")
+        );
+        assert!(read("2024-03-05", Some("%d/%m/%Y")).is_err());
+        assert!(read("someday", None).is_err());
+    }
+
+    #[test]
+    fn relative_datetimes() {
+        assert_eq!(
+            read("now", None).as_deref(),
+            Ok("2024-01-10T12:00:00+00:00")
+        );
+        assert_eq!(
+            read("Yesterday", None).as_deref(),
+            Ok("2024-01-09T00:00:00+00:00")
+        );
+        assert_eq!(
+            read("3 days ago", None).as_deref(),
+            Ok("2024-01-07T12:00:00+00:00")
+        );
+        assert_eq!(
+            read("an hour ago", None).as_deref(),
+            Ok("2024-01-10T11:00:00+00:00")
+        );
+        assert_eq!(
+            read("2 months ago", None).as_deref(),
+            Ok("2023-11-10T12:00:00+00:00")
+        );
+        assert!(read("3 fortnights ago", None).is_err());
+    }
+
+    #[test]
+    fn too_long_ago() {
+        for text in [
+            "200000000 days ago",
+            "4294967295 weeks ago",
+            "4294967295 months ago",
+            "4294967295 years ago",
+        ] {
+            assert!(read(text, None).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use url::Url;
+
+use crate::coerce;
+use crate::tree::{children, name, resolve};
+use crate::Node;
+
+/// An RSS or Atom feed.
+#[derive(async_graphql::SimpleObject)]
+pub struct Feed {
+    title: Option<String>,
+    /// the site the feed is for
+    link: Option<String>,
+    items: Vec<FeedItem>,
+}
+
+/// An RSS item or Atom entry.
+#[derive(async_graphql::SimpleObject)]
+pub struct FeedItem {
+    title: Option<String>,
+    link: Option<String>,
+    /// in RFC 3339 if it could be read, otherwise as it is in the feed
+    published: Option<String>,
+    /// RSS's guid or Atom's id
+    guid: Option<String>,
+    /// RSS's description or Atom's summary, or the content if there's neither
+    summary: Option<String>,
+    /// the `<item>` or `<entry>`, for anything else in it
+    node: Node,
+}
+
+/// None unless `node` is in an RSS 2.0, RSS 1.0 or Atom document. Best read with the XML
+/// parser; the HTML one drops CDATA sections, which descriptions are often in.
+pub fn feed(node: &Node) -> Option<Feed> {
+    let document = node.page.document.lock().unwrap();
+    let root = feed_root(&document)?;
+    let base = node.page.base.as_ref();
+
+    let (channel, items) = match local_name(&root).as_str() {
+        "rss" => {
+            let channel = child(&root, "channel")?;
+            let items = children(&channel)
+                .filter(|item| local_name(item) == "item")
+                .collect::<Vec<_>>();
+            (channel, items)
+        }
+        /* RSS 1.0 has items next to the channel */
+        "rdf" => {
+            let items = children(&root)
+                .filter(|item| local_name(item) == "item")
+                .collect::<Vec<_>>();
+            (child(&root, "channel")?, items)
+        }
+        "feed" => {
+            let entries = children(&root)
+                .filter(|entry| local_name(entry) == "entry")
+                .map(|entry| FeedItem {
+                    title: child_text(&entry, "title"),
+                    link: atom_link(&entry, base),
+                    published: child_text(&entry, "published")
+                        .or_else(|| child_text(&entry, "updated"))
+                        .map(datetime),
+                    guid: child_text(&entry, "id"),
+                    summary: child_text(&entry, "summary")
+                        .or_else(|| child_text(&entry, "content")),
+                    node: node.at(entry.id),
+                })
+                .collect();
+
+            return Some(Feed {
+                title: child_text(&root, "title"),
+                link: atom_link(&root, base),
+                items: entries,
+            });
+        }
+        _ => return None,
+    };
+
+    let items = items
+        .into_iter()
+        .map(|item| FeedItem {
+            title: child_text(&item, "title"),
+            link: rss_link(&item).map(|link| resolve(base, &link)),
+            /* dc:date */
+            published: child_text(&item, "pubdate")
+                .or_else(|| child_text(&item, "date"))
+                .map(datetime),
+            guid: child_text(&item, "guid").or_else(|| {
+                let about = item.attr("about").or_else(|| item.attr("rdf:about"))?;
+                Some(about.to_string())
+            }),
+            /* content:encoded */
+            summary: child_text(&item, "description").or_else(|| child_text(&item, "encoded")),
+            node: node.at(item.id),
+        })
+        .collect();
+
+    Some(Feed {
+        title: child_text(&channel, "title"),
+        link: rss_link(&channel).map(|link| resolve(base, &link)),
+        items,
+    })
+}
+
+/// The document's first element, or the first in the body if it was parsed as HTML; the
+/// HTML parser puts elements it doesn't know there.
+fn feed_root<'a>(document: &'a nipper::Document) -> Option<nipper::Node<'a>> {
+    let root = children(&document.root()).find(|node| node.is_element())?;
+    if local_name(&root) != "html" {
+        return Some(root);
+    }
+    children(&child(&root, "body")?).find(|node| node.is_element())
+}
+
+/// An RSS `<link>`'s text. The HTML parser thinks `<link>` is empty, like it is in HTML,
+/// so the text ends up just after it instead.
+fn rss_link(node: &nipper::Node) -> Option<String> {
+    child_text(node, "link").or_else(|| {
+        let text = child(node, "link")?.next_sibling()?;
+        let text = text.is_text().then(|| text.text().trim().to_string())?;
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+/// The href of the `<link>` with no rel or rel="alternate", otherwise of the first one.
+fn atom_link(node: &nipper::Node, base: Option<&Url>) -> Option<String> {
+    let links = children(node)
+        .filter(|link| local_name(link) == "link")
+        .collect::<Vec<_>>();
+    let alternate = links.iter().find(|link| {
+        link.attr("rel")
+            .map_or(true, |rel| rel.trim().eq_ignore_ascii_case("alternate"))
+    });
+
+    alternate
+        .or_else(|| links.first())
+        .and_then(|link| link.attr("href"))
+        .map(|href| resolve(base, &href))
+}
+
+fn datetime(text: String) -> String {
+    coerce::datetime(&text, None, chrono::Utc::now())
+        .map(|t| t.to_rfc3339())
+        .unwrap_or(text)
+}
+
+/// The element's name in lower case without any namespace prefix. The XML parser drops
+/// prefixes and keeps the case, so it gives "pubDate" and "date" where the HTML parser
+/// gives "pubdate" and "dc:date".
+fn local_name(node: &nipper::Node) -> String {
+    let name = name(node).to_ascii_lowercase();
+    match name.rsplit_once(':') {
+        Some((_, local)) => local.to_string(),
+        None => name,
+    }
+}
+
+/// The trimmed text of the first child element with this lower case local_name, if it
+/// has any.
+fn child_text(node: &nipper::Node, name: &str) -> Option<String> {
+    let text = child(node, name)?.text().trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn child<'a>(node: &nipper::Node<'a>, name: &str) -> Option<nipper::Node<'a>> {
+    children(node).find(|child| local_name(child) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup::Parser;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:content="http://purl.org/rss/1.0/modules/content/">
+  <channel>
+    <title>Fish</title>
+    <link>https://example.com/</link>
+    <item>
+      <title>Salmon</title>
+      <link>https://example.com/salmon</link>
+      <pubDate>Mon, 06 Sep 2021 16:45:00 +0000</pubDate>
+      <description>Pink</description>
+    </item>
+    <item>
+      <title>Cod</title>
+      <dc:date>2021-09-07T08:00:00Z</dc:date>
+      <content:encoded>White</content:encoded>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn either_parser() {
+        for parser in [Parser::Xml, Parser::Html] {
+            let feed = feed(&Node::parse(RSS, None, parser)).unwrap();
+            assert_eq!(feed.title.as_deref(), Some("Fish"), "{:?}", parser);
+            assert_eq!(feed.link.as_deref(), Some("https://example.com/"));
+
+            let items = feed
+                .items
+                .iter()
+                .map(|item| {
+                    (
+                        item.title.as_deref(),
+                        item.link.as_deref(),
+                        item.published.as_deref(),
+                        item.summary.as_deref(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                items,
+                [
+                    (
+                        Some("Salmon"),
+                        Some("https://example.com/salmon"),
+                        Some("2021-09-06T16:45:00+00:00"),
+                        Some("Pink")
+                    ),
+                    (
+                        Some("Cod"),
+                        None,
+                        Some("2021-09-07T08:00:00+00:00"),
+                        Some("White")
+                    ),
+                ],
+                "{:?}",
+                parser
+            );
+        }
+    }
+}
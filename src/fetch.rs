@@ -0,0 +1,727 @@
+use anyhow::Context;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::cache::{self, Cache};
+use crate::record::{self, Recorder};
+use crate::robots::Robots;
+
+const DEFAULT_CONCURRENCY: usize = 8;
+const DEFAULT_HOST_CONCURRENCY: usize = 2;
+const ROBOTS_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Fetch settings from the command line, shared by one-off queries and serve.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// most requests in flight at once
+    pub concurrency: usize,
+    /// most requests in flight at once to any one host
+    pub host_concurrency: usize,
+    /// least time between starting requests to the same host
+    pub delay: Duration,
+    /// sent with requests that don't set their own
+    pub user_agent: String,
+    /// refuse requests disallowed by robots.txt
+    pub robots: bool,
+    /// None to not cache at all
+    pub cache: Option<cache::Mode>,
+    pub cache_dir: Option<PathBuf>,
+    pub record: Option<(record::Mode, PathBuf)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            concurrency: DEFAULT_CONCURRENCY,
+            host_concurrency: DEFAULT_HOST_CONCURRENCY,
+            delay: Duration::ZERO,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            robots: false,
+            cache: Some(cache::Mode::Normal),
+            cache_dir: None,
+            record: None,
+        }
+    }
+}
+
+impl Options {
+    /// Takes `arg`, and its value from `argv`, if it's one of ours. Returns false otherwise.
+    pub fn parse_arg(
+        &mut self,
+        arg: &str,
+        argv: &mut impl Iterator<Item = String>,
+    ) -> anyhow::Result<bool> {
+        match arg {
+            "--concurrency" => {
+                self.concurrency = parse_value(arg, argv)?;
+                anyhow::ensure!(self.concurrency > 0, "--concurrency must be at least 1");
+            }
+            "--host-concurrency" => {
+                self.host_concurrency = parse_value(arg, argv)?;
+                anyhow::ensure!(
+                    self.host_concurrency > 0,
+                    "--host-concurrency must be at least 1"
+                );
+            }
+            "--delay-ms" => self.delay = Duration::from_millis(parse_value(arg, argv)?),
+            "--user-agent" => self.user_agent = parse_value(arg, argv)?,
+            "--robots" => self.robots = true,
+            "--no-cache" => self.cache = None,
+            "--offline" => self.cache = Some(cache::Mode::Offline),
+            "--refresh" => self.cache = Some(cache::Mode::Refresh),
+            "--cache-dir" => self.cache_dir = Some(parse_value(arg, argv)?),
+            "--record" => self.record = Some((record::Mode::Record, parse_value(arg, argv)?)),
+            "--replay" => self.record = Some((record::Mode::Replay, parse_value(arg, argv)?)),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+fn parse_value<T>(arg: &str, argv: &mut impl Iterator<Item = String>) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let value = argv
+        .next()
+        .with_context(|| format!("{} requires a value", arg))?;
+    value
+        .parse()
+        .with_context(|| format!("invalid value for {}: {:?}", arg, value))
+}
+
+/// Schema data; makes requests for Query.get and friends.
+///
+/// ureq blocks, so requests are queued for a pool of `Options::concurrency` threads and
+/// the resolver waits on them without holding up the executor. That lets every `get` and
+/// `follow` in a query run at the same time, up to that many at once; the rest wait in the
+/// queue, not on threads of their own.
+///
+/// To be polite, requests to the same host are also limited to `Options::host_concurrency`
+/// at once and are started at least `Options::delay` (or `Request::delay`) apart. Requests
+/// over either budget wait their turn; they don't fail. They wait before they're queued
+/// for the pool, so a busy host doesn't keep it from requests to other hosts.
+///
+/// With `Options::robots`, a request disallowed by the host's robots.txt for the user agent
+/// it's sent with fails with robots::Disallowed instead of being sent. robots.txt is
+/// fetched through the Fetcher too, so it waits for the host and is cached and recorded.
+///
+/// Responses found in the Cache skip all of that. And with `Options::record` set to
+/// replay, everything comes from the Recorder and nothing goes over the network.
+pub struct Fetcher {
+    pool: Arc<Pool>,
+    hosts: Arc<Hosts>,
+    delay: Duration,
+    user_agent: String,
+    robots: Option<Robots>,
+    cache: Option<Arc<Cache>>,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl Fetcher {
+    pub fn new(options: &Options) -> Self {
+        Fetcher {
+            pool: Pool::new(options.concurrency),
+            hosts: Hosts::new(options.host_concurrency),
+            delay: options.delay,
+            user_agent: options.user_agent.clone(),
+            robots: options.robots.then(Robots::default),
+            cache: options.cache.map(|mode| {
+                let dir = options.cache_dir.clone().unwrap_or_else(Cache::default_dir);
+                Arc::new(Cache::new(dir, mode))
+            }),
+            recorder: options
+                .record
+                .clone()
+                .map(|(mode, dir)| Arc::new(Recorder::new(dir, mode))),
+        }
+    }
+
+    pub async fn fetch(&self, mut request: Request) -> anyhow::Result<Response> {
+        if request.header("User-Agent").is_none() {
+            let user_agent = self.user_agent.clone();
+            request.headers.push(("User-Agent".to_string(), user_agent));
+        }
+
+        let delay = request.delay.map_or(self.delay, |d| d.max(self.delay));
+        let allow_error_status = request.allow_error_status;
+
+        /* first see if it can be answered without the network, which needn't wait on the host */
+        let local = if self.cache.is_some() || self.recorder.is_some() {
+            match self.run(request.clone(), None).await {
+                Err(err) if err.downcast_ref::<NeedsNetwork>().is_some() => None,
+                result => Some(result?),
+            }
+        } else {
+            None
+        };
+
+        let response = match local {
+            Some(response) => response,
+            None => {
+                if let Some(robots) = &self.robots {
+                    self.check_robots(robots, &request).await?;
+                }
+
+                let permit = self.hosts.acquire(host_of(&request.url), delay).await;
+                self.run(request, Some(permit)).await?
+            }
+        };
+
+        /* checked here, not in send, so error pages are still recorded and replayed */
+        if response.status >= 400 && !allow_error_status {
+            return Err(ErrorStatus {
+                url: response.url,
+                status: response.status,
+            }
+            .into());
+        }
+
+        Ok(response)
+    }
+
+    /// Answers the request on the pool, from the Recorder, the Cache or the network. It
+    /// only goes over the network with a permit for the host; without one, requests that
+    /// would fail with NeedsNetwork.
+    async fn run(&self, request: Request, permit: Option<HostPermit>) -> anyhow::Result<Response> {
+        let cache = self.cache.clone();
+        let recorder = self.recorder.clone();
+
+        self.pool
+            .run(move || {
+                let send = |request: &Request| {
+                    if permit.is_none() {
+                        return Err(NeedsNetwork.into());
+                    }
+
+                    request.send()
+                };
+
+                let fetch = |request: &Request| match cache {
+                    Some(cache) => cache.fetch(request, send),
+                    None => send(request),
+                };
+
+                let response = match recorder {
+                    Some(recorder) => recorder.fetch(&request, fetch),
+                    None => fetch(&request),
+                };
+
+                /* hold on to the host until the request is done */
+                drop(permit);
+                response
+            })
+            .await?
+    }
+
+    /// Fetches robots.txt for the request's origin if we need it, then checks the request
+    /// against it.
+    async fn check_robots(&self, robots: &Robots, request: &Request) -> anyhow::Result<()> {
+        let user_agent = request.header("User-Agent").unwrap_or_default();
+
+        if let Some(robots_url) = robots.wanted(&request.url)? {
+            let mut get = Request::get(robots_url.clone());
+            get.headers
+                .push(("User-Agent".to_string(), user_agent.to_string()));
+            get.timeout = Some(ROBOTS_TIMEOUT);
+            get.delay = request.delay;
+            get.allow_error_status = true;
+
+            /* boxed because fetch calls this; wanted() is None for robots.txt, so it ends */
+            let fetch: Pin<Box<dyn Future<Output = anyhow::Result<Response>> + Send + '_>> =
+                Box::pin(self.fetch(get));
+            robots.store(robots_url, fetch.await);
+        }
+
+        robots.check(&request.url, user_agent)
+    }
+}
+
+/// Why Fetcher::run didn't answer a request; it has to wait for the host.
+#[derive(Debug)]
+struct NeedsNetwork;
+
+impl std::fmt::Display for NeedsNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "can't answer this without the network")
+    }
+}
+
+impl std::error::Error for NeedsNetwork {}
+
+/// The error for a response with a 4xx or 5xx status, unless its request allowed them.
+#[derive(Debug)]
+pub struct ErrorStatus {
+    pub url: String,
+    pub status: u16,
+}
+
+impl std::fmt::Display for ErrorStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: status code {}", self.url, self.status)
+    }
+}
+
+impl std::error::Error for ErrorStatus {}
+
+fn host_of(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            let host = url.host_str()?.to_owned();
+            Some(match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        })
+        .unwrap_or_default()
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed number of threads taking jobs off a queue in the order they were added. The
+/// threads live as long as the process does.
+struct Pool {
+    queue: Mutex<VecDeque<Job>>,
+    queued: Condvar,
+}
+
+impl Pool {
+    fn new(threads: usize) -> Arc<Self> {
+        let pool = Arc::new(Pool {
+            queue: Mutex::new(VecDeque::new()),
+            queued: Condvar::new(),
+        });
+
+        for _ in 0..threads {
+            let pool = Arc::clone(&pool);
+            std::thread::spawn(move || loop {
+                let job = {
+                    let mut queue = pool.queue.lock().unwrap();
+                    loop {
+                        match queue.pop_front() {
+                            Some(job) => break job,
+                            None => queue = pool.queued.wait(queue).unwrap(),
+                        }
+                    }
+                };
+                job();
+            });
+        }
+
+        pool
+    }
+
+    /// Queues `f`, resolving to its result when one of the threads has run it.
+    fn run<F, T>(&self, f: F) -> impl Future<Output = anyhow::Result<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        struct Shared<T> {
+            value: Option<anyhow::Result<T>>,
+            waker: Option<Waker>,
+        }
+
+        let shared = Arc::new(Mutex::new(Shared {
+            value: None,
+            waker: None,
+        }));
+
+        let theirs = Arc::clone(&shared);
+        let job = move || {
+            /* don't leave the future pending forever, or lose the thread, if f panics */
+            let value = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+                .map_err(|_| anyhow::anyhow!("fetch thread panicked"));
+            let mut shared = theirs.lock().unwrap();
+            shared.value = Some(value);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        };
+
+        self.queue.lock().unwrap().push_back(Box::new(job));
+        self.queued.notify_one();
+
+        std::future::poll_fn(move |cx| {
+            let mut shared = shared.lock().unwrap();
+            match shared.value.take() {
+                Some(value) => Poll::Ready(value),
+                None => {
+                    shared.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+}
+
+/// Per host in-flight counts and start times.
+struct Hosts {
+    limit: usize,
+    hosts: Mutex<HashMap<String, Host>>,
+    timer: Arc<Timer>,
+}
+
+#[derive(Default)]
+struct Host {
+    in_flight: usize,
+    not_before: Option<Instant>,
+    /// tasks to wake when a request to the host finishes
+    waiting: Vec<Waker>,
+}
+
+impl Hosts {
+    fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Hosts {
+            limit,
+            hosts: Mutex::new(HashMap::new()),
+            timer: Timer::new(),
+        })
+    }
+
+    /// Resolves when a request to `host` may start, holding one of its slots and keeping
+    /// the next request from starting until `delay` has passed.
+    fn acquire(
+        self: &Arc<Self>,
+        host: String,
+        delay: Duration,
+    ) -> impl Future<Output = HostPermit> {
+        let hosts = Arc::clone(self);
+        let mut host = Some(host);
+
+        std::future::poll_fn(move |cx| {
+            let name = host.as_ref().expect("polled after it was ready");
+            let mut map = hosts.hosts.lock().unwrap();
+            let now = Instant::now();
+            let entry = map.entry(name.clone()).or_default();
+
+            if entry.in_flight >= hosts.limit {
+                entry.waiting.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+
+            match entry.not_before {
+                Some(not_before) if not_before > now => {
+                    hosts.timer.wake_at(not_before, cx.waker().clone());
+                    Poll::Pending
+                }
+                _ => {
+                    entry.in_flight += 1;
+                    entry.not_before = Some(now + delay);
+                    drop(map);
+                    Poll::Ready(HostPermit {
+                        hosts: Arc::clone(&hosts),
+                        host: host.take().unwrap_or_default(),
+                    })
+                }
+            }
+        })
+    }
+}
+
+struct HostPermit {
+    hosts: Arc<Hosts>,
+    host: String,
+}
+
+impl Drop for HostPermit {
+    fn drop(&mut self) {
+        let waiting = match self.hosts.hosts.lock().unwrap().get_mut(&self.host) {
+            Some(entry) => {
+                entry.in_flight -= 1;
+                std::mem::take(&mut entry.waiting)
+            }
+            None => vec![],
+        };
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+}
+
+/// Wakes tasks at the times they ask for, from one thread that lives as long as the
+/// process does.
+struct Timer {
+    wakers: Mutex<Vec<(Instant, Waker)>>,
+    changed: Condvar,
+}
+
+impl Timer {
+    fn new() -> Arc<Self> {
+        let timer = Arc::new(Timer {
+            wakers: Mutex::new(vec![]),
+            changed: Condvar::new(),
+        });
+
+        let theirs = Arc::clone(&timer);
+        std::thread::spawn(move || {
+            let mut wakers = theirs.wakers.lock().unwrap();
+            loop {
+                let now = Instant::now();
+                wakers.retain(|(at, waker)| {
+                    let due = *at <= now;
+                    if due {
+                        waker.wake_by_ref();
+                    }
+                    !due
+                });
+
+                wakers = match wakers.iter().map(|(at, _)| *at).min() {
+                    Some(next) => theirs.changed.wait_timeout(wakers, next - now).unwrap().0,
+                    None => theirs.changed.wait(wakers).unwrap(),
+                };
+            }
+        });
+
+        timer
+    }
+
+    fn wake_at(&self, at: Instant, waker: Waker) {
+        self.wakers.lock().unwrap().push((at, waker));
+        self.changed.notify_one();
+    }
+}
+
+#[derive(async_graphql::Enum, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Method {
+    #[default]
+    Get,
+    Head,
+    Post,
+    Put,
+    Patch,
+    Delete,
+    Options,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+        }
+    }
+}
+
+#[derive(async_graphql::InputObject, Clone, Debug)]
+pub struct HeaderInput {
+    pub name: String,
+    pub value: String,
+}
+
+/// Everything needed to make one HTTP request on behalf of a query.
+#[derive(Clone, Debug, Default)]
+pub struct Request {
+    pub url: String,
+    pub method: Method,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    pub timeout: Option<Duration>,
+    /// not sent; how long to wait after this before starting another request to the host
+    pub delay: Option<Duration>,
+    /// not sent; return responses with 4xx and 5xx statuses instead of failing
+    pub allow_error_status: bool,
+}
+
+impl Request {
+    pub fn get(url: impl Into<String>) -> Self {
+        Request {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Responses with 4xx and 5xx statuses are returned like any other; Fetcher decides
+    /// whether they're an error.
+    pub fn send(&self) -> anyhow::Result<Response> {
+        let started = Instant::now();
+
+        let mut req = ureq::request(self.method.as_str(), &self.url);
+
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+
+        for (name, value) in self.headers.iter() {
+            req = req.set(name, value);
+        }
+
+        let res = match &self.body {
+            Some(body) => req.send_string(body),
+            None => req.call(),
+        };
+
+        let res = match res {
+            Ok(res) | Err(ureq::Error::Status(_, res)) => res,
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut headers: Vec<(String, String)> = vec![];
+        for name in res.headers_names() {
+            if headers.iter().any(|(seen, _)| *seen == name) {
+                continue;
+            }
+            for value in res.all(&name) {
+                headers.push((name.clone(), value.to_string()));
+            }
+        }
+
+        let url = res.get_url().to_string();
+        let status = res.status();
+        let status_text = res.status_text().to_string();
+        let body = res.into_string()?;
+
+        Ok(Response {
+            url,
+            status,
+            status_text,
+            headers,
+            elapsed: started.elapsed(),
+            body,
+            from_cache: false,
+        })
+    }
+}
+
+#[derive(async_graphql::SimpleObject, Clone, Debug)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+/// What came back from a Request. The body is taken out and parsed into a Document, the
+/// rest is kept around for queries to look at.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Response {
+    /// after following redirects
+    pub url: String,
+    pub status: u16,
+    pub status_text: String,
+    /// names are lower case, in the order they were received
+    pub headers: Vec<(String, String)>,
+    pub elapsed: Duration,
+    pub body: String,
+    #[serde(skip)]
+    pub from_cache: bool,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The media type from the Content-Type header without any parameters.
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("content-type")
+            .map(|v| v.split(';').next().unwrap_or_default().trim())
+    }
+}
+
+#[async_graphql::Object]
+impl Response {
+    async fn status(&self) -> u16 {
+        self.status
+    }
+
+    async fn status_text(&self) -> &str {
+        &self.status_text
+    }
+
+    async fn final_url(&self) -> &str {
+        &self.url
+    }
+
+    async fn headers(&self) -> Vec<Header> {
+        self.headers
+            .iter()
+            .map(|(name, value)| Header {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect()
+    }
+
+    #[graphql(name = "header")]
+    async fn header_(&self, name: String) -> Option<&str> {
+        self.header(&name)
+    }
+
+    #[graphql(name = "contentType")]
+    async fn content_type_(&self) -> Option<&str> {
+        self.content_type()
+    }
+
+    async fn elapsed_ms(&self) -> u64 {
+        self.elapsed.as_millis() as u64
+    }
+
+    /// Whether this was read from the cache, including after revalidating with the server.
+    async fn from_cache(&self) -> bool {
+        self.from_cache
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every request with an empty 200 until the test process exits.
+    fn serve() -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let _ = request.respond(tiny_http::Response::empty(200));
+            }
+        });
+        format!("http://{}/", addr)
+    }
+
+    #[test]
+    fn delayed_host_doesnt_hold_up_others() {
+        let (slow, fast) = (serve(), serve());
+        let fetcher = Arc::new(Fetcher::new(&Options {
+            concurrency: 1,
+            host_concurrency: 1,
+            cache: None,
+            ..Default::default()
+        }));
+
+        let mut request = Request::get(&slow);
+        request.delay = Some(Duration::from_secs(10));
+        extreme::run(fetcher.fetch(request)).unwrap();
+
+        /* waits out the delay; with one pool thread, it mustn't be waiting on that */
+        let theirs = Arc::clone(&fetcher);
+        std::thread::spawn(move || extreme::run(theirs.fetch(Request::get(slow))));
+        std::thread::sleep(Duration::from_millis(100));
+
+        let started = Instant::now();
+        extreme::run(fetcher.fetch(Request::get(fast))).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,16 @@
+/// FNV-1a of the parts. Unlike std's DefaultHasher, it's the same between builds, so
+/// it's fine to write down.
+pub fn hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for part in parts {
+        /* length first so ("ab", "c") and ("a", "bc") differ */
+        let len = (part.len() as u64).to_le_bytes();
+        for byte in len.iter().chain(part.as_bytes()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    hash
+}
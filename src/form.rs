@@ -0,0 +1,349 @@
+use nipper::{MatchScope, Matcher, Matches};
+use url::Url;
+
+use crate::fetch::{self, Method};
+use crate::fnv;
+use crate::text::{self, TextOptions};
+use crate::tree::name;
+use crate::{fetch_error, Node};
+
+/// A `<form>`, with what a browser would send if it were submitted as it is.
+pub struct Form {
+    action: Option<String>,
+    method: Method,
+    enctype: String,
+    fields: Vec<Field>,
+    /// names and values submitted unless submit() is told otherwise
+    defaults: Vec<(String, String)>,
+}
+
+#[derive(async_graphql::SimpleObject)]
+pub struct Field {
+    name: String,
+    /// the input's type, like "text" or "checkbox"; "select" or "textarea"; or the
+    /// button's type
+    #[graphql(name = "type")]
+    kind: String,
+    /// the value attribute, a textarea's text or the first selected option of a select
+    value: Option<String>,
+    checked: bool,
+    disabled: bool,
+    options: Vec<FieldOption>,
+}
+
+#[derive(async_graphql::SimpleObject)]
+pub struct FieldOption {
+    value: String,
+    text: String,
+    selected: bool,
+}
+
+#[derive(async_graphql::InputObject)]
+pub struct FieldInput {
+    name: String,
+    value: String,
+}
+
+const ENCTYPES: &[&str] = &[
+    "application/x-www-form-urlencoded",
+    "multipart/form-data",
+    "text/plain",
+];
+
+impl Form {
+    /// `url` is the document's own; it's where a form without an action goes. Controls
+    /// outside the form that name it in their `form` attribute are included.
+    pub fn new(
+        form: nipper::Node,
+        root: nipper::Node,
+        base: Option<&Url>,
+        url: Option<&Url>,
+    ) -> Form {
+        let action = match form.attr("action").filter(|a| !a.trim().is_empty()) {
+            Some(action) => match base {
+                Some(base) => base.join(action.trim()).ok(),
+                None => Url::parse(action.trim()).ok(),
+            },
+            None => url.cloned(),
+        };
+
+        let method = match form.attr("method") {
+            Some(m) if m.trim().eq_ignore_ascii_case("post") => Method::Post,
+            _ => Method::Get,
+        };
+
+        let enctype = form
+            .attr("enctype")
+            .map(|e| e.trim().to_ascii_lowercase())
+            .filter(|e| ENCTYPES.contains(&e.as_str()))
+            .unwrap_or_else(|| ENCTYPES[0].to_string());
+
+        let form_id = form.attr("id").map(|id| id.to_string());
+        let owner = form.id;
+
+        let controls: Vec<nipper::Node> = Matcher::new("input, select, textarea, button")
+            .map(|matcher| Matches::from_one(root, matcher, MatchScope::IncludeNode).collect())
+            .unwrap_or_else(|_| vec![]);
+
+        let mut fields = vec![];
+        let mut defaults = vec![];
+
+        for control in controls {
+            let owned = match control.attr("form") {
+                Some(id) => form_id.as_deref() == Some(&*id),
+                None => std::iter::successors(control.parent(), |node| node.parent())
+                    .find(|node| name(node) == "form")
+                    .map_or(false, |node| node.id == owner),
+            };
+            if !owned {
+                continue;
+            }
+
+            if let Some(field) = Field::new(control) {
+                defaults.extend(field.defaults());
+                fields.push(field);
+            }
+        }
+
+        Form {
+            action: action.map(String::from),
+            method,
+            enctype,
+            fields,
+            defaults,
+        }
+    }
+
+    /// The request submitting the form would make, with `values` in place of the
+    /// defaults for the names they have.
+    fn request(&self, values: Vec<FieldInput>) -> anyhow::Result<fetch::Request> {
+        let action = self
+            .action
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("the form has nowhere to submit to"))?;
+
+        let mut pairs = self.defaults.clone();
+        pairs.retain(|(name, _)| !values.iter().any(|v| v.name == *name));
+        pairs.extend(values.into_iter().map(|v| (v.name, v.value)));
+
+        if self.method == Method::Get {
+            let mut url = Url::parse(action)?;
+            url.set_query(None);
+            if !pairs.is_empty() {
+                url.query_pairs_mut().extend_pairs(&pairs);
+            }
+            return Ok(fetch::Request::get(url));
+        }
+
+        let (content_type, body) = match self.enctype.as_str() {
+            "multipart/form-data" => multipart(&pairs),
+            "text/plain" => (
+                "text/plain".to_string(),
+                pairs
+                    .iter()
+                    .map(|(name, value)| format!("{}={}\r\n", name, value))
+                    .collect(),
+            ),
+            _ => (
+                self.enctype.clone(),
+                url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(&pairs)
+                    .finish(),
+            ),
+        };
+
+        let mut request = fetch::Request::get(action);
+        request.method = self.method;
+        request.headers = vec![("Content-Type".to_string(), content_type)];
+        request.body = Some(body);
+        Ok(request)
+    }
+}
+
+#[async_graphql::Object]
+impl Form {
+    /// The absolute URL the form submits to.
+    async fn action(&self) -> Option<&str> {
+        self.action.as_deref()
+    }
+
+    async fn method(&self) -> Method {
+        self.method
+    }
+
+    async fn enctype(&self) -> &str {
+        &self.enctype
+    }
+
+    /// Named controls in the form, buttons included.
+    async fn fields(&self) -> &Vec<Field> {
+        &self.fields
+    }
+
+    /// Submits the form like a browser would without anyone touching it, except that a
+    /// name in `values` replaces whatever the form had for it. Files can't be uploaded.
+    /// Error statuses are handled like Query.get does.
+    async fn submit(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        values: Option<Vec<FieldInput>>,
+        #[graphql(default)] allow_error_status: bool,
+    ) -> async_graphql::Result<Node> {
+        let mut request = self.request(values.unwrap_or_default())?;
+        request.allow_error_status = allow_error_status;
+        Node::fetch(ctx.data_unchecked(), request, None)
+            .await
+            .map_err(fetch_error)
+    }
+}
+
+impl Field {
+    /// None for controls without a name, which are never submitted.
+    fn new(control: nipper::Node) -> Option<Field> {
+        let name = control.attr("name")?.to_string();
+        if name.is_empty() {
+            return None;
+        }
+
+        let tag = self::name(&control);
+        let attr = |attr: &str| control.attr(attr).map(|value| value.to_string());
+        let disabled = attr("disabled").is_some();
+
+        let kind = match tag.as_str() {
+            "input" => attr("type")
+                .map(|t| t.trim().to_ascii_lowercase())
+                .unwrap_or_else(|| "text".to_string()),
+            "button" => attr("type")
+                .map(|t| t.trim().to_ascii_lowercase())
+                .unwrap_or_else(|| "submit".to_string()),
+            tag => tag.to_string(),
+        };
+        let checked = (kind == "checkbox" || kind == "radio") && attr("checked").is_some();
+
+        let field = match tag.as_str() {
+            "select" => {
+                let multiple = attr("multiple").is_some();
+                let options = select_options(control, multiple);
+                Field {
+                    name,
+                    kind,
+                    value: options
+                        .iter()
+                        .find(|option| option.selected)
+                        .map(|option| option.value.clone()),
+                    checked,
+                    disabled,
+                    options,
+                }
+            }
+            "textarea" => Field {
+                name,
+                kind,
+                value: Some(text::text(control, &TextOptions::default())),
+                checked,
+                disabled,
+                options: vec![],
+            },
+            _ => {
+                let value = match kind.as_str() {
+                    "checkbox" | "radio" => attr("value").or_else(|| Some("on".to_string())),
+                    _ => attr("value").or_else(|| Some(String::new())),
+                };
+                Field {
+                    name,
+                    kind,
+                    value,
+                    checked,
+                    disabled,
+                    options: vec![],
+                }
+            }
+        };
+
+        Some(field)
+    }
+
+    fn defaults(&self) -> Vec<(String, String)> {
+        let pair = |value: &str| (self.name.clone(), value.to_string());
+
+        match self.kind.as_str() {
+            _ if self.disabled => vec![],
+            /* buttons are only sent when they're what was clicked */
+            "submit" | "reset" | "button" | "image" | "file" => vec![],
+            "checkbox" | "radio" if !self.checked => vec![],
+            "select" => self
+                .options
+                .iter()
+                .filter(|option| option.selected)
+                .map(|option| pair(&option.value))
+                .collect(),
+            _ => self.value.iter().map(|value| pair(value)).collect(),
+        }
+    }
+}
+
+/// Without a selected option, the first is selected unless the select is `multiple`.
+fn select_options(select: nipper::Node, multiple: bool) -> Vec<FieldOption> {
+    let options = TextOptions::normalized();
+
+    let mut found = Matcher::new("option")
+        .map(|matcher| {
+            Matches::from_one(select, matcher, MatchScope::IncludeNode)
+                .filter(|option| option.attr("disabled").is_none())
+                .map(|option| {
+                    let selected = option.attr("selected").is_some();
+                    let value = option.attr("value").map(|value| value.to_string());
+                    let text = text::text(option, &options);
+                    FieldOption {
+                        value: value.unwrap_or_else(|| text.clone()),
+                        text,
+                        selected,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if !multiple && !found.iter().any(|option| option.selected) {
+        if let Some(first) = found.first_mut() {
+            first.selected = true;
+        }
+    }
+
+    found
+}
+
+/// A multipart/form-data body and the Content-Type naming its boundary.
+fn multipart(pairs: &[(String, String)]) -> (String, String) {
+    /* from the pairs, not the time, so the same form gives the same request and cache key */
+    let parts = pairs
+        .iter()
+        .flat_map(|(name, value)| [name.as_str(), value.as_str()])
+        .collect::<Vec<_>>();
+    let mut hash = fnv::hash(&parts);
+    let boundary = loop {
+        let boundary = format!("----{}{:016x}", env!("CARGO_PKG_NAME"), hash);
+        if !parts.iter().any(|part| part.contains(&boundary)) {
+            break boundary;
+        }
+        hash = fnv::hash(&[&boundary]);
+    };
+    let escape = |name: &str| {
+        name.replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+
+    let mut body = String::new();
+    for (name, value) in pairs {
+        body.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            boundary,
+            escape(name),
+            value
+        ));
+    }
+    body.push_str(&format!("--{}--\r\n", boundary));
+
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
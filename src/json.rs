@@ -0,0 +1,716 @@
+use async_graphql::{InputValueError, Json};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::fetch::{self, Fetcher};
+
+/// A JSON document and the response it came from, shared by every JsonNode in it.
+struct Document {
+    value: Value,
+    response: Option<fetch::Response>,
+}
+
+/// A value in a JSON document.
+pub struct JsonNode {
+    document: Arc<Document>,
+    /// where in the document, as a JSON pointer
+    pointer: String,
+}
+
+impl JsonNode {
+    pub async fn fetch(fetcher: &Fetcher, request: fetch::Request) -> anyhow::Result<JsonNode> {
+        let mut response = fetcher.fetch(request).await?;
+        let body = std::mem::take(&mut response.body);
+        let value = serde_json::from_str(&body)
+            .map_err(|err| anyhow::anyhow!("{} didn't return JSON: {}", response.url, err))?;
+        Ok(JsonNode {
+            document: Arc::new(Document {
+                value,
+                response: Some(response),
+            }),
+            pointer: String::new(),
+        })
+    }
+
+    fn value(&self) -> &Value {
+        self.document
+            .value
+            .pointer(&self.pointer)
+            .unwrap_or(&Value::Null)
+    }
+
+    fn at(&self, pointer: String) -> JsonNode {
+        JsonNode {
+            document: Arc::clone(&self.document),
+            pointer,
+        }
+    }
+
+    /// Pointers to the values in this one, with their values.
+    fn children(&self) -> Vec<(String, &Value)> {
+        children(&self.pointer, self.value())
+    }
+}
+
+#[async_graphql::Object(name = "Json")]
+impl JsonNode {
+    /// The HTTP response this document was read from.
+    async fn response(&self) -> Option<&fetch::Response> {
+        self.document.response.as_ref()
+    }
+
+    #[graphql(name = "value")]
+    async fn value_(&self) -> Json<Value> {
+        Json(self.value().clone())
+    }
+
+    /// Where this is in the document, as a JSON pointer.
+    async fn location(&self) -> &str {
+        &self.pointer
+    }
+
+    /// The keys of an object; empty for anything else.
+    async fn keys(&self) -> Vec<String> {
+        match self.value() {
+            Value::Object(map) => map.keys().cloned().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The items in an array, or the values in an object.
+    async fn items(&self) -> Vec<JsonNode> {
+        self.children()
+            .into_iter()
+            .map(|(pointer, _)| self.at(pointer))
+            .collect()
+    }
+
+    /// An object's value by key, or an array's item by index.
+    async fn get(&self, key: String) -> Option<JsonNode> {
+        let pointer = format!("{}/{}", self.pointer, escape(&key));
+        self.document
+            .value
+            .pointer(&pointer)
+            .is_some()
+            .then(|| self.at(pointer))
+    }
+
+    /// The value at a JSON pointer like `/items/0/name`, relative to this one.
+    async fn pointer(&self, ptr: String) -> async_graphql::Result<Option<JsonNode>> {
+        if !ptr.is_empty() && !ptr.starts_with('/') {
+            return Err(format!("{:?} isn't a JSON pointer, they start with /", ptr).into());
+        }
+        let pointer = format!("{}{}", self.pointer, ptr);
+        Ok(self
+            .document
+            .value
+            .pointer(&pointer)
+            .is_some()
+            .then(|| self.at(pointer)))
+    }
+
+    /// Values matched by a JSONPath like `$.items[*].name`, with `$` being this value.
+    async fn path(&self, json_path: JsonPath) -> Vec<JsonNode> {
+        json_path
+            .select(&self.pointer, self.value())
+            .into_iter()
+            .map(|(pointer, _)| self.at(pointer))
+            .collect()
+    }
+
+    /// The string, number or boolean as a string; null for anything else.
+    async fn text(&self) -> Option<String> {
+        match self.value() {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// A JSONPath, parsed when the query is.
+///
+/// This understands names (`.a`, `['a']`), indexes (`[0]`, `[-1]`), slices (`[1:3]`),
+/// wildcards, unions (`[0,2]`), descent (`..a`) and simple filters like
+/// `[?(@.price < 10)]` or `[?(@.isbn)]`.
+pub struct JsonPath(Vec<Step>, String);
+
+struct Step {
+    /// `..`; this and every value under it
+    descend: bool,
+    selectors: Vec<Selector>,
+}
+
+enum Selector {
+    Name(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, i64),
+    Wildcard,
+    Filter(Filter),
+}
+
+struct Filter {
+    /// names from `@`
+    path: Vec<String>,
+    /// without one, the filter is whether there's anything at `path`
+    compare: Option<(Compare, Value)>,
+}
+
+#[derive(Clone, Copy)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[async_graphql::Scalar(name = "JsonPath")]
+impl async_graphql::ScalarType for JsonPath {
+    fn parse(value: async_graphql::Value) -> Result<Self, InputValueError<Self>> {
+        if let async_graphql::Value::String(s) = value {
+            Parser::new(&s)
+                .path()
+                .map(|steps| JsonPath(steps, s.clone()))
+                .map_err(|err| InputValueError::custom(format!("{} in {:?}", err, s)))
+        } else {
+            Err(InputValueError::custom("expected jsonpath string"))
+        }
+    }
+
+    fn to_value(&self) -> async_graphql::Value {
+        async_graphql::Value::String(self.1.clone())
+    }
+}
+
+impl JsonPath {
+    fn select<'v>(&self, pointer: &str, value: &'v Value) -> Vec<(String, &'v Value)> {
+        let mut current = vec![(pointer.to_string(), value)];
+
+        for step in &self.0 {
+            let mut next = vec![];
+            for (pointer, value) in current {
+                let visit = if step.descend {
+                    descendants(pointer, value)
+                } else {
+                    vec![(pointer, value)]
+                };
+                for (pointer, value) in visit {
+                    for selector in &step.selectors {
+                        next.extend(selector.select(&pointer, value));
+                    }
+                }
+            }
+            current = next;
+        }
+
+        current
+    }
+}
+
+impl Selector {
+    fn select<'v>(&self, pointer: &str, value: &'v Value) -> Vec<(String, &'v Value)> {
+        let child = |key: &str| format!("{}/{}", pointer, escape(key));
+
+        match (self, value) {
+            (Selector::Name(name), Value::Object(map)) => map
+                .get(name)
+                .map(|value| (child(name), value))
+                .into_iter()
+                .collect(),
+            (Selector::Index(i), Value::Array(items)) => {
+                let i = if *i < 0 { items.len() as i64 + i } else { *i };
+                usize::try_from(i)
+                    .ok()
+                    .and_then(|i| items.get(i).map(|value| (child(&i.to_string()), value)))
+                    .into_iter()
+                    .collect()
+            }
+            (Selector::Slice(start, end, step), Value::Array(items)) => {
+                slice(items.len(), *start, *end, *step)
+                    .into_iter()
+                    .map(|i| (child(&i.to_string()), &items[i]))
+                    .collect()
+            }
+            (Selector::Wildcard, _) => children(pointer, value),
+            (Selector::Filter(filter), _) => children(pointer, value)
+                .into_iter()
+                .filter(|(_, value)| filter.matches(value))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, value: &Value) -> bool {
+        let mut found = value;
+        for name in &self.path {
+            found = match found {
+                Value::Object(map) => match map.get(name) {
+                    Some(value) => value,
+                    None => return false,
+                },
+                Value::Array(items) => {
+                    match name.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                        Some(value) => value,
+                        None => return false,
+                    }
+                }
+                _ => return false,
+            };
+        }
+
+        let (compare, literal) = match &self.compare {
+            Some(compare) => compare,
+            None => return true,
+        };
+
+        let ordering = match (found, literal) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) if a == b => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        };
+
+        use std::cmp::Ordering::*;
+        match (compare, ordering) {
+            (Compare::Eq, Some(Equal)) => true,
+            (Compare::Ne, Some(Equal)) => false,
+            (Compare::Ne, _) => true,
+            (Compare::Lt, Some(Less)) => true,
+            (Compare::Le, Some(Less | Equal)) => true,
+            (Compare::Gt, Some(Greater)) => true,
+            (Compare::Ge, Some(Greater | Equal)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Indexes of a slice like Python's; a negative start or end counts from the end. A step
+/// past the end of i64 stops the slice rather than wrapping around.
+fn slice(len: usize, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let len = len as i64;
+    let clamp = |i: i64, low: i64, high: i64| {
+        let i = if i < 0 { len + i } else { i };
+        i.max(low).min(high)
+    };
+
+    let mut indexes = vec![];
+    if step > 0 {
+        let mut i = clamp(start.unwrap_or(0), 0, len);
+        let end = clamp(end.unwrap_or(len), 0, len);
+        while i < end {
+            indexes.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(i) => i,
+                None => break,
+            };
+        }
+    } else if step < 0 {
+        let mut i = clamp(start.unwrap_or(len - 1), -1, len - 1);
+        let end = end.map_or(-1, |end| clamp(end, -1, len - 1));
+        while i > end {
+            indexes.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(i) => i,
+                None => break,
+            };
+        }
+    }
+    indexes
+}
+
+fn children<'v>(pointer: &str, value: &'v Value) -> Vec<(String, &'v Value)> {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (format!("{}/{}", pointer, escape(key)), value))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (format!("{}/{}", pointer, i), value))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// This value and everything under it, in document order.
+fn descendants(pointer: String, value: &Value) -> Vec<(String, &Value)> {
+    let mut found = vec![];
+    let mut stack = vec![(pointer, value)];
+    while let Some((pointer, value)) = stack.pop() {
+        stack.extend(children(&pointer, value).into_iter().rev());
+        found.push((pointer, value));
+    }
+    found
+}
+
+/// A key as a JSON pointer reference token.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+struct Parser<'s> {
+    s: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn new(s: &'s str) -> Self {
+        Parser {
+            s: s.trim(),
+            pos: 0,
+        }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.s[self.pos..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected {:?} at {}", token, self.pos))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn path(&mut self) -> Result<Vec<Step>, String> {
+        let _ = self.eat("$");
+        let mut steps = vec![];
+
+        while !self.rest().is_empty() {
+            if self.eat("..") {
+                let selectors = if self.eat("[") {
+                    self.bracket()?
+                } else {
+                    vec![self.dotted()?]
+                };
+                steps.push(Step {
+                    descend: true,
+                    selectors,
+                });
+            } else if self.eat(".") {
+                steps.push(Step {
+                    descend: false,
+                    selectors: vec![self.dotted()?],
+                });
+            } else if self.eat("[") {
+                steps.push(Step {
+                    descend: false,
+                    selectors: self.bracket()?,
+                });
+            } else {
+                return Err(format!("unexpected {:?} at {}", self.rest(), self.pos));
+            }
+        }
+
+        Ok(steps)
+    }
+
+    /// What comes after a `.`
+    fn dotted(&mut self) -> Result<Selector, String> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        let name = self.name();
+        if name.is_empty() {
+            return Err(format!("expected a name at {}", self.pos));
+        }
+        Ok(Selector::Name(name.to_string()))
+    }
+
+    fn name(&mut self) -> &'s str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '$'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// What's between `[` and `]`, after the `[`.
+    fn bracket(&mut self) -> Result<Vec<Selector>, String> {
+        let mut selectors = vec![];
+        loop {
+            selectors.push(self.selector()?);
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, String> {
+        self.skip_whitespace();
+
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        if self.eat("?") {
+            let parens = self.eat("(");
+            let filter = self.filter()?;
+            if parens {
+                self.expect(")")?;
+            }
+            return Ok(Selector::Filter(filter));
+        }
+        if let Some(s) = self.string()? {
+            return Ok(Selector::Name(s));
+        }
+
+        let start = self.integer()?;
+        if !self.eat(":") {
+            return start
+                .map(Selector::Index)
+                .ok_or_else(|| format!("expected a selector at {}", self.pos));
+        }
+        let end = self.integer()?;
+        let step = if self.eat(":") { self.integer()? } else { None };
+        Ok(Selector::Slice(start, end, step.unwrap_or(1)))
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+            .map_or(rest.len(), |(i, _)| i);
+        if len == 0 {
+            return Ok(None);
+        }
+        self.pos += len;
+        rest[..len]
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("bad number {:?}", &rest[..len]))
+    }
+
+    /// A quoted string, if there's one here.
+    fn string(&mut self) -> Result<Option<String>, String> {
+        self.skip_whitespace();
+        let quote = match self.rest().chars().next() {
+            Some(quote @ ('\'' | '"')) => quote,
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+
+        let mut s = String::new();
+        let mut chars = self.rest().chars();
+        while let Some(c) = chars.next() {
+            self.pos += c.len_utf8();
+            match c {
+                '\\' => {
+                    let escaped = chars.next().ok_or("unterminated string")?;
+                    self.pos += escaped.len_utf8();
+                    s.push(escaped);
+                }
+                c if c == quote => return Ok(Some(s)),
+                c => s.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn filter(&mut self) -> Result<Filter, String> {
+        self.expect("@")?;
+
+        let mut path = vec![];
+        loop {
+            if self.eat(".") {
+                let name = self.name();
+                if name.is_empty() {
+                    return Err(format!("expected a name at {}", self.pos));
+                }
+                path.push(name.to_string());
+            } else if self.eat("[") {
+                match self.string()? {
+                    Some(name) => path.push(name),
+                    None => match self.integer()? {
+                        Some(i) => path.push(i.to_string()),
+                        None => return Err(format!("expected a name at {}", self.pos)),
+                    },
+                }
+                self.expect("]")?;
+            } else {
+                break;
+            }
+        }
+
+        let compare = [
+            ("==", Compare::Eq),
+            ("!=", Compare::Ne),
+            ("<=", Compare::Le),
+            (">=", Compare::Ge),
+            ("<", Compare::Lt),
+            (">", Compare::Gt),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token))
+        .map(|(_, compare)| compare);
+
+        let compare = match compare {
+            Some(compare) => Some((compare, self.literal()?)),
+            None => None,
+        };
+
+        Ok(Filter { path, compare })
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        if let Some(s) = self.string()? {
+            return Ok(Value::String(s));
+        }
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c == ')' || c == ']' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        self.pos += len;
+        serde_json::from_str(&rest[..len]).map_err(|_| format!("bad value {:?}", &rest[..len]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(path: &str, value: &Value) -> Vec<String> {
+        let steps = Parser::new(path).path().unwrap();
+        JsonPath(steps, path.to_string())
+            .select("", value)
+            .into_iter()
+            .map(|(pointer, _)| pointer)
+            .collect()
+    }
+
+    fn store() -> Value {
+        serde_json::json!({
+            "store": {
+                "book": [
+                    {"category": "reference", "title": "Sayings of the Century", "price": 8.95},
+                    {"category": "fiction", "title": "Sword of Honour", "price": 12.99},
+                    {"category": "fiction", "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99}
+                ],
+                "bicycle": {"color": "red", "price": 19.95}
+            },
+            "a/b~": 1
+        })
+    }
+
+    #[test]
+    fn names_and_indexes() {
+        let store = store();
+        assert_eq!(
+            select("$.store.book[0].title", &store),
+            ["/store/book/0/title"]
+        );
+        assert_eq!(
+            select("$.store.book[-1].title", &store),
+            ["/store/book/2/title"]
+        );
+        assert_eq!(
+            select("$['store'][\"bicycle\"].color", &store),
+            ["/store/bicycle/color"]
+        );
+        assert_eq!(select("$['a/b~']", &store), ["/a~1b~0"]);
+        assert_eq!(select("$.store.book[3]", &store), Vec::<String>::new());
+        assert_eq!(select("$.nothing.here", &store), Vec::<String>::new());
+    }
+
+    #[test]
+    fn wildcards_unions_and_descent() {
+        let store = store();
+        assert_eq!(
+            select("$.store.*", &store),
+            ["/store/book", "/store/bicycle"]
+        );
+        assert_eq!(
+            select("$.store.book[0, 2].price", &store),
+            ["/store/book/0/price", "/store/book/2/price"]
+        );
+        assert_eq!(
+            select("$..price", &store),
+            [
+                "/store/book/0/price",
+                "/store/book/1/price",
+                "/store/book/2/price",
+                "/store/bicycle/price"
+            ]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let store = store();
+        assert_eq!(
+            select("$.store.book[?(@.isbn)].title", &store),
+            ["/store/book/2/title"]
+        );
+        assert_eq!(
+            select("$.store.book[?(@.price < 10)].title", &store),
+            ["/store/book/0/title", "/store/book/2/title"]
+        );
+        assert_eq!(
+            select("$.store.book[?(@.category == 'fiction')]", &store),
+            ["/store/book/1", "/store/book/2"]
+        );
+        assert_eq!(
+            select("$.store.book[?(@.category != 'fiction')]", &store),
+            ["/store/book/0"]
+        );
+    }
+
+    #[test]
+    fn slices() {
+        assert_eq!(slice(5, Some(1), Some(3), 1), [1, 2]);
+        assert_eq!(slice(5, None, None, 2), [0, 2, 4]);
+        assert_eq!(slice(5, Some(-2), None, 1), [3, 4]);
+        assert_eq!(slice(5, None, None, -1), [4, 3, 2, 1, 0]);
+        assert_eq!(slice(3, Some(10), Some(-10), -1), [2, 1, 0]);
+        assert_eq!(slice(3, None, None, 0), Vec::<usize>::new());
+        assert_eq!(slice(0, None, None, 1), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn huge_steps() {
+        assert_eq!(slice(3, Some(1), None, i64::MAX), [1]);
+        assert_eq!(slice(3, None, None, i64::MIN), [2]);
+        assert_eq!(
+            select("$[1::9223372036854775807]", &serde_json::json!([0, 1, 2])),
+            ["/1"]
+        );
+    }
+
+    #[test]
+    fn bad_paths() {
+        for path in [
+            "$.",
+            "$[1:",
+            "$.store book",
+            "$['unterminated]",
+            "$[?(@.a == )]",
+        ] {
+            assert!(Parser::new(path).path().is_err(), "{}", path);
+        }
+    }
+}
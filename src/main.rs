@@ -1,7 +1,57 @@
 use anyhow::Context;
-use async_graphql::{InputValueError, Value};
+use async_graphql::{InputValueError, Json, Value};
 use nipper::{Document, MatchScope, Matcher, Matches, StrTendril};
 use std::sync::{Arc, Mutex};
+use url::Url;
+
+mod cache;
+mod coerce;
+mod feed;
+mod fetch;
+mod fnv;
+mod form;
+mod json;
+mod markup;
+mod output;
+mod pattern;
+mod record;
+mod robots;
+mod serve;
+mod structured;
+mod table;
+mod text;
+mod tree;
+mod xpath;
+
+use feed::Feed;
+use fetch::{Fetcher, HeaderInput, Method};
+use form::Form;
+use json::JsonNode;
+use markup::Parser;
+use pattern::{Captures, Group, Pattern};
+use table::Table;
+use text::TextOptions;
+use xpath::XPath;
+
+type Schema =
+    async_graphql::Schema<Query, async_graphql::EmptyMutation, async_graphql::EmptySubscription>;
+
+fn schema(
+    options: &fetch::Options,
+) -> async_graphql::SchemaBuilder<
+    Query,
+    async_graphql::EmptyMutation,
+    async_graphql::EmptySubscription,
+> {
+    use async_graphql::{EmptyMutation, EmptySubscription};
+    Schema::build(Query, EmptyMutation, EmptySubscription).data(Fetcher::new(options))
+}
+
+/// Schema data; present when `Query.file` may read from the local filesystem.
+struct AllowFiles;
+
+/// Schema data; an HTML document read from stdin for `Query.stdin`.
+struct Stdin(String);
 
 struct Selector(Matcher, String);
 
@@ -28,26 +78,216 @@ struct Query;
 
 #[async_graphql::Object]
 impl Query {
-    async fn get(&self, url: String) -> anyhow::Result<Node> {
-        let body = ureq::get(&url).call()?.into_string()?;
-        let document = Document::from(&body);
-        let id = document.root().id;
-        let document = Arc::new(Mutex::new(document));
-        Ok(Node { document, id })
+    /// `delayMs` keeps the next request to this host from starting until it has passed;
+    /// if --delay-ms is longer, that's used instead. Without a `parser`, the response is
+    /// read as XML if its Content-Type says it's XML, otherwise as HTML.
+    ///
+    /// A 4xx or 5xx status is an error with the code HTTP_ERROR_STATUS, unless
+    /// `allowErrorStatus` is set; then the page is returned like any other, for the query to
+    /// look at `response { status }`.
+    #[allow(clippy::too_many_arguments)]
+    async fn get(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        url: String,
+        method: Option<Method>,
+        headers: Option<Vec<HeaderInput>>,
+        body: Option<String>,
+        timeout_ms: Option<u64>,
+        user_agent: Option<String>,
+        delay_ms: Option<u64>,
+        parser: Option<Parser>,
+        #[graphql(default)] allow_error_status: bool,
+    ) -> async_graphql::Result<Node> {
+        let mut request = request(url, method, headers, body, timeout_ms, user_agent, delay_ms);
+        request.allow_error_status = allow_error_status;
+        Node::fetch(ctx.data_unchecked(), request, parser)
+            .await
+            .map_err(fetch_error)
+    }
+
+    /// Like get, but for a JSON API. Asks for JSON with an Accept header unless `headers`
+    /// has one. With `allowErrorStatus`, an error status's body has to be JSON too.
+    #[allow(clippy::too_many_arguments)]
+    async fn get_json(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        url: String,
+        method: Option<Method>,
+        headers: Option<Vec<HeaderInput>>,
+        body: Option<String>,
+        timeout_ms: Option<u64>,
+        user_agent: Option<String>,
+        delay_ms: Option<u64>,
+        #[graphql(default)] allow_error_status: bool,
+    ) -> async_graphql::Result<JsonNode> {
+        let mut request = request(url, method, headers, body, timeout_ms, user_agent, delay_ms);
+        request.allow_error_status = allow_error_status;
+        if request.header("accept").is_none() {
+            request
+                .headers
+                .push(("Accept".to_string(), "application/json".to_string()));
+        }
+        JsonNode::fetch(ctx.data_unchecked(), request)
+            .await
+            .map_err(fetch_error)
+    }
+
+    async fn file(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        path: String,
+        #[graphql(default)] parser: Parser,
+    ) -> anyhow::Result<Node> {
+        ctx.data_opt::<AllowFiles>()
+            .context("reading files is not allowed here")?;
+        let body = std::fs::read_to_string(&path).with_context(|| format!("read {}", path))?;
+        let url = std::fs::canonicalize(&path)
+            .ok()
+            .and_then(|path| Url::from_file_path(path).ok());
+        Ok(Node::parse(&body, url, parser))
+    }
+
+    /// Relative URLs in the document are resolved against `baseUrl`, if given.
+    async fn parse(
+        &self,
+        html: String,
+        base_url: Option<String>,
+        #[graphql(default)] parser: Parser,
+    ) -> anyhow::Result<Node> {
+        let url = base_url
+            .map(|url| Url::parse(&url))
+            .transpose()
+            .context("invalid baseUrl")?;
+        Ok(Node::parse(&html, url, parser))
+    }
+
+    async fn stdin(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] parser: Parser,
+    ) -> anyhow::Result<Node> {
+        let Stdin(body) = ctx
+            .data_opt::<Stdin>()
+            .context("pass --stdin-html to read a document from stdin")?;
+        Ok(Node::parse(body, None, parser))
+    }
+}
+
+/// The request for Query.get's arguments.
+fn request(
+    url: String,
+    method: Option<Method>,
+    headers: Option<Vec<HeaderInput>>,
+    body: Option<String>,
+    timeout_ms: Option<u64>,
+    user_agent: Option<String>,
+    delay_ms: Option<u64>,
+) -> fetch::Request {
+    let mut request = fetch::Request::get(url);
+    request.method = method.unwrap_or_default();
+    request.body = body;
+    request.timeout = timeout_ms.map(std::time::Duration::from_millis);
+    request.delay = delay_ms.map(std::time::Duration::from_millis);
+    request.headers = headers
+        .unwrap_or_default()
+        .into_iter()
+        .map(|HeaderInput { name, value }| (name, value))
+        .chain(user_agent.map(|ua| ("User-Agent".to_string(), ua)))
+        .collect();
+    request
+}
+
+/// Gives errors a client might want to handle a `code` in their extensions.
+fn fetch_error(err: anyhow::Error) -> async_graphql::Error {
+    use async_graphql::ErrorExtensions;
+
+    let error = async_graphql::Error::new(err.to_string());
+
+    if err.downcast_ref::<robots::Disallowed>().is_some() {
+        error.extend_with(|_, e| e.set("code", "ROBOTS_DISALLOWED"))
+    } else if let Some(status) = err.downcast_ref::<fetch::ErrorStatus>() {
+        let status = i32::from(status.status);
+        error.extend_with(|_, e| {
+            e.set("code", "HTTP_ERROR_STATUS");
+            e.set("status", status);
+        })
+    } else {
+        error
+    }
+}
+
+/// A parsed document and whatever we know about where it came from, shared by every Node
+/// in it.
+struct Page {
+    document: Mutex<Document>,
+    /// relative URLs in the document are resolved against this
+    base: Option<Url>,
+    response: Option<fetch::Response>,
+}
+
+impl Page {
+    fn new(document: Document, url: Option<Url>, response: Option<fetch::Response>) -> Page {
+        let base = document_base(&document, url);
+        Page {
+            document: Mutex::new(document),
+            base,
+            response,
+        }
+    }
+}
+
+/// The document's own url unless it has a `<base href>` saying otherwise.
+fn document_base(document: &Document, url: Option<Url>) -> Option<Url> {
+    let matcher = Matcher::new("base[href]").ok()?;
+    let href = Matches::from_one(document.root(), matcher, MatchScope::IncludeNode)
+        .find_map(|base| base.attr("href"));
+
+    match (url, href) {
+        (Some(url), Some(href)) => Some(url.join(href.trim()).unwrap_or(url)),
+        (None, Some(href)) => Url::parse(href.trim()).ok(),
+        (url, None) => url,
     }
 }
 
 struct Node {
-    document: Arc<Mutex<Document>>,
+    page: Arc<Page>,
     id: nipper::NodeId,
 }
 
 impl Node {
+    fn root(page: Page) -> Node {
+        let id = page.document.lock().unwrap().root().id;
+        let page = Arc::new(page);
+        Node { page, id }
+    }
+
+    fn parse(text: &str, url: Option<Url>, parser: Parser) -> Node {
+        Node::root(Page::new(parser.parse(text), url, None))
+    }
+
+    /// Without a parser, one is picked from the response's Content-Type.
+    async fn fetch(
+        fetcher: &Fetcher,
+        request: fetch::Request,
+        parser: Option<Parser>,
+    ) -> anyhow::Result<Node> {
+        let mut response = fetcher.fetch(request).await?;
+        let body = std::mem::take(&mut response.body);
+        let url = Url::parse(&response.url).ok();
+        let parser = parser.unwrap_or_else(|| Parser::for_content_type(response.content_type()));
+        Ok(Node::root(Page::new(
+            parser.parse(&body),
+            url,
+            Some(response),
+        )))
+    }
+
     fn with_node<F, R>(&self, f: F) -> R
     where
         F: FnOnce(nipper::Node) -> R,
     {
-        let document = self.document.lock().unwrap();
+        let document = self.page.document.lock().unwrap();
         let node = document.node(self.id);
         f(node)
     }
@@ -57,12 +297,109 @@ impl Node {
             .as_ref()
             .map(StrTendril::to_string)
     }
+
+    fn text_content(&self) -> String {
+        self.with_node(|node| text::text(node, &TextOptions::default()))
+    }
+
+    /// What regex fields look at; the attribute if one is named, otherwise the text.
+    fn subject(&self, attr: Option<&str>) -> Option<String> {
+        match attr {
+            Some(attr) => self.attr(attr),
+            None => Some(self.text_content()),
+        }
+    }
+
+    /// Parses the text, or `attr` if given, with `f`. Null if there's no such attribute,
+    /// and null with an error if `f` fails.
+    fn coerced<T, F>(&self, attr: Option<&str>, f: F) -> async_graphql::Result<Option<T>>
+    where
+        F: FnOnce(&str) -> Result<T, String>,
+    {
+        match self.subject(attr) {
+            Some(subject) => f(&subject).map(Some).map_err(async_graphql::Error::new),
+            None => Ok(None),
+        }
+    }
+
+    /// The attribute's value as an absolute URL, resolved against the document's base.
+    fn abs_url(&self, attr: &str) -> Option<String> {
+        let value = self.attr(attr)?;
+        let value = value.trim();
+        let url = match &self.page.base {
+            Some(base) => base.join(value),
+            None => Url::parse(value),
+        };
+        url.ok().map(String::from)
+    }
+
+    /// Another node in the same document.
+    fn at(&self, id: nipper::NodeId) -> Node {
+        Node {
+            page: Arc::clone(&self.page),
+            id,
+        }
+    }
+
+    fn step<F>(&self, f: F) -> Option<Node>
+    where
+        F: FnOnce(nipper::Node) -> Option<nipper::Node>,
+    {
+        self.with_node(|node| f(node).map(|next| next.id))
+            .map(|id| self.at(id))
+    }
+
+    /// This node and the elements under it, or only those matching the selector.
+    fn descendant_ids(&self, select: Option<Selector>) -> Vec<nipper::NodeId> {
+        self.with_node(|node| match select {
+            Some(Selector(mut matcher, _)) => {
+                matcher.scope = Some(self.id);
+                Matches::from_one(node, matcher, MatchScope::IncludeNode)
+                    .map(|matched| matched.id)
+                    .collect()
+            }
+            None => tree::walk(node)
+                .filter(|node| node.is_element())
+                .map(|node| node.id)
+                .collect(),
+        })
+    }
+
+    /// Parent, grandparent, and so on, nearest first, starting with this node if `itself`.
+    /// Only the elements matching the selector if there is one, with `:scope` being this
+    /// node; each is tested on its own rather than selecting from the whole document.
+    fn ancestor_ids(&self, itself: bool, select: Option<Selector>) -> Vec<nipper::NodeId> {
+        let matcher = select.map(|Selector(mut matcher, _)| {
+            matcher.scope = Some(self.id);
+            matcher
+        });
+
+        let document = self.page.document.lock().unwrap();
+        let node = document.node(self.id);
+        let first = if itself { Some(node) } else { node.parent() };
+
+        std::iter::successors(first, |node| node.parent())
+            .map(|node| node.id)
+            .filter(|id| match &matcher {
+                Some(matcher) => {
+                    let node = document.node(*id);
+                    node.is_element() && nipper::Selection::from(node).is_matcher(matcher)
+                }
+                None => true,
+            })
+            .collect()
+    }
 }
 
 #[async_graphql::Object]
 impl Node {
+    /// The HTTP response this node's document was read from, if it came from one.
+    async fn response(&self) -> Option<&fetch::Response> {
+        self.page.response.as_ref()
+    }
+
     async fn this_text(&self) -> Option<String> {
-        let document = self.document.lock().unwrap();
+        let document = self.page.document.lock().unwrap();
         let node = document.node(self.id);
         node.is_text().then(|| node.text().to_string())
     }
@@ -76,19 +413,225 @@ impl Node {
         self.attr("href")
     }
 
+    /// The href resolved to an absolute URL; null if there isn't enough to resolve it
+    /// against.
+    async fn abs_href(&self) -> Option<String> {
+        self.abs_url("href")
+    }
+
+    #[graphql(name = "absUrl")]
+    async fn abs_url_(&self, attr: String) -> Option<String> {
+        self.abs_url(&attr)
+    }
+
+    /// The src resolved to an absolute URL.
+    async fn src(&self) -> Option<String> {
+        self.abs_url("src")
+    }
+
+    /// Fetches the URL in `attr` (`href` by default), resolved like absUrl, and returns the
+    /// root of the document that comes back. Null if there's no URL to follow. Error
+    /// statuses are handled like get does.
+    async fn follow(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        attr: Option<String>,
+        delay_ms: Option<u64>,
+        #[graphql(default)] allow_error_status: bool,
+    ) -> async_graphql::Result<Option<Node>> {
+        let attr = attr.as_deref().unwrap_or("href");
+        let url = match self.abs_url(attr) {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        let mut request = fetch::Request::get(url);
+        request.delay = delay_ms.map(std::time::Duration::from_millis);
+        request.allow_error_status = allow_error_status;
+        Node::fetch(ctx.data_unchecked(), request, None)
+            .await
+            .map(Some)
+            .map_err(fetch_error)
+    }
+
+    /// What relative URLs in this document are resolved against.
+    async fn base_url(&self) -> Option<String> {
+        self.page.base.as_ref().map(Url::to_string)
+    }
+
     async fn class(&self) -> Vec<String> {
         self.attr("class")
             .map(|s| s.split_ascii_whitespace().map(ToOwned::to_owned).collect())
             .unwrap_or_default()
     }
 
-    async fn text(&self) -> String {
-        let document = self.document.lock().unwrap();
-        let this = document.node(self.id);
-        walk(this)
-            .filter(|node| node.is_text())
-            .map(|node| node.text().to_string())
-            .collect::<String>()
+    /// All the text in and under this node. By default it's exactly as it is in the
+    /// document; `normalize` collapses whitespace, `trim` trims it and `separator` goes
+    /// between block elements. Using any of them also leaves out the contents of
+    /// `<script>` and `<style>`.
+    async fn text(
+        &self,
+        #[graphql(default)] normalize: bool,
+        #[graphql(default)] trim: bool,
+        separator: Option<String>,
+    ) -> String {
+        let options = TextOptions {
+            normalize,
+            trim,
+            separator,
+        };
+        self.with_node(|node| text::text(node, &options))
+    }
+
+    /// Text as a browser might show it; normalized, trimmed and with a line per block.
+    async fn inner_text(&self) -> String {
+        self.with_node(|node| text::text(node, &TextOptions::inner_text()))
+    }
+
+    /// The first match of `regex` in the text, or in `attr` if given. `group` picks the
+    /// capture group to return, by default the whole match.
+    #[graphql(name = "match")]
+    async fn match_(
+        &self,
+        regex: Pattern,
+        group: Option<Group>,
+        attr: Option<String>,
+    ) -> Option<String> {
+        let subject = self.subject(attr.as_deref())?;
+        let captures = regex.0.captures(&subject)?;
+        let group = group.unwrap_or_default();
+        group.get(&captures).map(|m| m.as_str().to_string())
+    }
+
+    /// Every match of `regex` in the text, or in `attr` if given.
+    async fn matches(&self, regex: Pattern, attr: Option<String>) -> Vec<Captures> {
+        let Pattern(regex) = regex;
+        self.subject(attr.as_deref())
+            .map(|subject| {
+                regex
+                    .captures_iter(&subject)
+                    .map(|captures| Captures::new(&regex, &captures))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The text, or `attr` if given, with every match of `regex` replaced. `with` can
+    /// refer to capture groups as `$1` or `${name}`.
+    async fn replace(&self, regex: Pattern, with: String, attr: Option<String>) -> Option<String> {
+        let subject = self.subject(attr.as_deref())?;
+        Some(regex.0.replace_all(&subject, with.as_str()).into_owned())
+    }
+
+    /// The first number in the text, or in `attr` if given, ignoring currency symbols and
+    /// thousands separators. `locale`, like "de" or "en-US", says whether "," or "." is the
+    /// decimal point; otherwise it's guessed.
+    async fn number(
+        &self,
+        locale: Option<String>,
+        attr: Option<String>,
+    ) -> async_graphql::Result<Option<f64>> {
+        self.coerced(attr.as_deref(), |s| coerce::number(s, locale.as_deref()))
+    }
+
+    /// Like number, but it has to be a whole one.
+    async fn integer(
+        &self,
+        locale: Option<String>,
+        attr: Option<String>,
+    ) -> async_graphql::Result<Option<i64>> {
+        self.coerced(attr.as_deref(), |s| coerce::integer(s, locale.as_deref()))
+    }
+
+    /// Reads words like true, yes, on and 1 from the text, or from `attr` if given. Like
+    /// HTML's boolean attributes, one that's present but empty, like `<input checked>`, is
+    /// true and one that's missing is false.
+    async fn boolean(&self, attr: Option<String>) -> async_graphql::Result<Option<bool>> {
+        let value = self.coerced(attr.as_deref(), |s| match attr.as_deref() {
+            Some(attr) if s.is_empty() || s.eq_ignore_ascii_case(attr) => Ok(true),
+            _ => coerce::boolean(s),
+        })?;
+        Ok(value.or(attr.map(|_| false)))
+    }
+
+    /// Reads a date and time from the text, or from `attr` if given, and returns it in RFC
+    /// 3339. `format` is strftime syntax like "%d/%m/%Y"; without it, a few common formats
+    /// and things like "3 days ago" are understood.
+    async fn datetime(
+        &self,
+        format: Option<String>,
+        attr: Option<String>,
+    ) -> async_graphql::Result<Option<String>> {
+        self.coerced(attr.as_deref(), |s| {
+            coerce::datetime(s, format.as_deref(), chrono::Utc::now()).map(|t| t.to_rfc3339())
+        })
+    }
+
+    /// Forms in or under this node.
+    async fn forms(&self) -> Vec<Form> {
+        let url = match &self.page.response {
+            Some(response) => Url::parse(&response.url).ok(),
+            None => self.page.base.clone(),
+        };
+
+        let document = self.page.document.lock().unwrap();
+        let ids = Matcher::new("form")
+            .map(|matcher| {
+                Matches::from_one(document.node(self.id), matcher, MatchScope::IncludeNode)
+                    .map(|form| form.id)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        ids.into_iter()
+            .map(|id| {
+                Form::new(
+                    document.node(id),
+                    document.root(),
+                    self.page.base.as_ref(),
+                    url.as_ref(),
+                )
+            })
+            .collect()
+    }
+
+    /// The JSON-LD in the document, from every `<script type="application/ld+json">`.
+    async fn json_ld(&self) -> Json<Vec<serde_json::Value>> {
+        let document = self.page.document.lock().unwrap();
+        Json(structured::json_ld(document.root()))
+    }
+
+    /// The document's top level microdata items.
+    async fn microdata(&self) -> Json<Vec<serde_json::Value>> {
+        let document = self.page.document.lock().unwrap();
+        Json(structured::microdata(
+            document.root(),
+            self.page.base.as_ref(),
+        ))
+    }
+
+    /// OpenGraph and other `<meta property>` tags in the document, keyed by property.
+    async fn open_graph(&self) -> Json<serde_json::Map<String, serde_json::Value>> {
+        let document = self.page.document.lock().unwrap();
+        Json(structured::open_graph(document.root()))
+    }
+
+    /// The content of the document's `<meta>` with this name, like "description", or
+    /// property, like "og:title".
+    async fn meta(&self, name: String) -> Option<String> {
+        let document = self.page.document.lock().unwrap();
+        structured::meta(document.root(), &name)
+    }
+
+    /// The items in the RSS or Atom feed this node is in; null if it isn't in one.
+    async fn feed(&self) -> Option<Feed> {
+        feed::feed(self)
+    }
+
+    /// This `<table>` as headers and rows, or as records keyed by header; null for
+    /// anything that isn't a table.
+    async fn table(&self) -> Option<Table> {
+        self.with_node(Table::new)
     }
 
     async fn html(&self) -> String {
@@ -102,16 +645,77 @@ impl Node {
             .unwrap_or_default()
     }
 
+    async fn parent(&self) -> Option<Node> {
+        self.step(|node| node.parent())
+    }
+
+    /// Child nodes, including text.
+    async fn children(&self) -> Vec<Node> {
+        self.with_node(|node| {
+            tree::children(&node)
+                .map(|node| node.id)
+                .collect::<Vec<_>>()
+        })
+        .into_iter()
+        .map(|id| self.at(id))
+        .collect()
+    }
+
+    async fn first_child(&self) -> Option<Node> {
+        self.step(|node| node.first_child())
+    }
+
+    async fn last_child(&self) -> Option<Node> {
+        self.step(|node| node.last_child())
+    }
+
+    async fn next_sibling(&self) -> Option<Node> {
+        self.step(|node| node.next_sibling())
+    }
+
+    async fn prev_sibling(&self) -> Option<Node> {
+        self.step(|node| node.prev_sibling())
+    }
+
+    /// Like nextSibling but skips over text and comments.
+    async fn next_element_sibling(&self) -> Option<Node> {
+        self.step(|node| {
+            std::iter::successors(node.next_sibling(), |node| node.next_sibling())
+                .find(|node| node.is_element())
+        })
+    }
+
+    /// Like prevSibling but skips over text and comments.
+    async fn prev_element_sibling(&self) -> Option<Node> {
+        self.step(|node| {
+            std::iter::successors(node.prev_sibling(), |node| node.prev_sibling())
+                .find(|node| node.is_element())
+        })
+    }
+
+    /// Parent, grandparent, and so on; nearest first. Optionally only those matching the
+    /// selector.
+    async fn ancestors(&self, select: Option<Selector>) -> Vec<Node> {
+        self.ancestor_ids(false, select)
+            .into_iter()
+            .map(|id| self.at(id))
+            .collect()
+    }
+
+    /// This node or the nearest ancestor matching the selector.
+    async fn closest(&self, select: Selector) -> Option<Node> {
+        self.ancestor_ids(true, Some(select))
+            .first()
+            .map(|id| self.at(*id))
+    }
+
     async fn select(&self, select: Selector) -> Vec<Node> {
         let Selector(mut matcher, _) = select;
         matcher.scope = Some(self.id);
 
         self.with_node(|node| {
             Matches::from_one(node, matcher, MatchScope::IncludeNode)
-                .map(move |matched| Node {
-                    document: Arc::clone(&self.document),
-                    id: matched.id,
-                })
+                .map(|matched| self.at(matched.id))
                 .collect()
         })
     }
@@ -122,29 +726,221 @@ impl Node {
 
         self.with_node(|node| {
             Matches::from_one(node, matcher, MatchScope::IncludeNode)
-                .map(move |matched| Node {
-                    document: Arc::clone(&self.document),
-                    id: matched.id,
-                })
+                .map(|matched| self.at(matched.id))
                 .next()
         })
     }
+
+    /// Elements whose text, normalized and trimmed, contains `text` (or is `text` if
+    /// `exact`) or matches `regex`. Only elements matching `within` are looked at, if
+    /// given. Where one match is inside another only the inner one is kept, so looking for
+    /// "Price" gives the `<td>` rather than the table around it.
+    async fn select_by_text(
+        &self,
+        text: Option<String>,
+        #[graphql(default)] exact: bool,
+        regex: Option<Pattern>,
+        within: Option<Selector>,
+    ) -> async_graphql::Result<Vec<Node>> {
+        if text.is_some() == regex.is_some() {
+            return Err("either text or regex is required, not both".into());
+        }
+        let wanted = |found: &str| match (&text, &regex) {
+            (Some(text), _) if exact => found == text,
+            (Some(text), _) => found.contains(text.as_str()),
+            (None, Some(Pattern(regex))) => regex.is_match(found),
+            (None, None) => false,
+        };
+
+        let options = TextOptions::normalized();
+        let candidates = self.descendant_ids(within);
+
+        let document = self.page.document.lock().unwrap();
+        let matched = candidates
+            .into_iter()
+            .filter(|id| wanted(&text::text(document.node(*id), &options)))
+            .collect::<Vec<_>>();
+
+        /* leave out anything with a match inside it */
+        let outer = matched
+            .iter()
+            .flat_map(|id| {
+                std::iter::successors(document.node(*id).parent(), |node| node.parent())
+                    .map(|node| node.id)
+                    .filter(|id| matched.contains(id))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(matched
+            .into_iter()
+            .filter(|id| !outer.contains(id))
+            .map(|id| self.at(id))
+            .collect())
+    }
+
+    /// Nodes selected by an XPath 1.0 expression, with this node as the context node.
+    /// Attributes in the result are left out; use xpathStrings for those.
+    async fn xpath(&self, path: XPath) -> async_graphql::Result<Vec<Node>> {
+        let ids = {
+            let document = self.page.document.lock().unwrap();
+            xpath::nodes(&document, self.id, &path)?
+        };
+        Ok(ids.into_iter().map(|id| self.at(id)).collect())
+    }
+
+    async fn xpath_one(&self, path: XPath) -> async_graphql::Result<Option<Node>> {
+        let ids = {
+            let document = self.page.document.lock().unwrap();
+            xpath::nodes(&document, self.id, &path)?
+        };
+        Ok(ids.into_iter().next().map(|id| self.at(id)))
+    }
+
+    /// The text of each node selected, or the value of each attribute, like with
+    /// `//a/@href`.
+    async fn xpath_strings(&self, path: XPath) -> async_graphql::Result<Vec<String>> {
+        let document = self.page.document.lock().unwrap();
+        Ok(xpath::strings(&document, self.id, &path)?)
+    }
+
+    /// The result of an expression like `normalize-space(//h1)` or `string(//a/@href)`;
+    /// for nodes it's the text of the first.
+    async fn xpath_string(&self, path: XPath) -> async_graphql::Result<String> {
+        let document = self.page.document.lock().unwrap();
+        Ok(xpath::string(&document, self.id, &path)?)
+    }
+
+    /// The result of an expression like `count(//li)`; null if it isn't a number.
+    async fn xpath_number(&self, path: XPath) -> async_graphql::Result<Option<f64>> {
+        let document = self.page.document.lock().unwrap();
+        let n = xpath::number(&document, self.id, &path)?;
+        Ok(n.is_finite().then_some(n))
+    }
 }
 
+const USAGE: &str = concat!(
+    "usage: ",
+    env!("CARGO_PKG_NAME"),
+    r#" [options] <query>
+       "#,
+    env!("CARGO_PKG_NAME"),
+    r#" [options] -f <file.graphql>
+       "#,
+    env!("CARGO_PKG_NAME"),
+    r#" serve [--listen ADDR] [--allow-files] [fetch options]
+
+Runs a GraphQL query and prints the result. Variables are read from stdin as a JSON
+object, unless there are --var options or --stdin-html.
+
+options:
+  -f, --query-file PATH   read the query from a file
+  --operation NAME        which operation to run, when the query has more than one
+  --var NAME=VALUE        set a variable to a string
+  --var NAME:=JSON        set a variable to any JSON value
+  --stdin-html            read a document from stdin for Query.stdin
+  --format FORMAT         json, json-pretty, ndjson, csv, tsv, atom or rss
+  --output FORMAT         the same as --format
+  --path PATH             what in the result to print, like get.select
+  -h, --help              print this and exit
+  -V, --version           print the version and exit
+
+fetch options:
+  --concurrency N         most requests at once (8)
+  --host-concurrency N    most requests at once to one host (2)
+  --delay-ms MS           least time between requests to one host
+  --user-agent UA         User-Agent for requests that don't set one
+  --robots                refuse requests robots.txt disallows
+  --no-cache              don't use the response cache
+  --offline               only use the cache, never the network; responses without
+                          max-age, an ETag or Last-Modified aren't cached
+  --refresh               ignore what's cached, but store what comes back
+  --cache-dir DIR         where the cache is kept
+  --record DIR            save every response to DIR
+  --replay DIR            answer every request from what was saved in DIR
+
+serve options:
+  --listen ADDR           where to take GraphQL requests (127.0.0.1:8080)
+  --allow-files           let queries read files with Query.file
+"#
+);
+
 fn main() -> anyhow::Result<()> {
     let mut argv = std::env::args();
     let _exe = argv
         .next()
         .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string());
-    let query = argv.next().context("graphql query required")?;
 
-    let vars = {
+    let mut query = None;
+    let mut stdin_html = false;
+    let mut output = output::Output::default();
+    let mut path = None;
+    let mut operation = None;
+    let mut vars = serde_json::Map::new();
+    let mut options = fetch::Options::default();
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "serve" if query.is_none() => return serve::main(options, argv),
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            "-V" | "--version" => {
+                println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+                return Ok(());
+            }
+            "-f" | "--query-file" => {
+                anyhow::ensure!(query.is_none(), "{} given with another query", arg);
+                let file = argv
+                    .next()
+                    .with_context(|| format!("{} requires a path", arg))?;
+                let text =
+                    std::fs::read_to_string(&file).with_context(|| format!("read {}", file))?;
+                query = Some(text);
+            }
+            "--operation" => operation = Some(argv.next().context("--operation requires a name")?),
+            "--var" => {
+                let var = argv.next().context("--var requires NAME=VALUE")?;
+                let (name, value) = parse_var(&var)?;
+                vars.insert(name, value);
+            }
+            "--stdin-html" => stdin_html = true,
+            "--format" | "--output" => {
+                output = argv
+                    .next()
+                    .with_context(|| format!("{} requires a value", arg))?
+                    .parse()?
+            }
+            "--path" => path = Some(argv.next().context("--path requires a value")?),
+            _ if options.parse_arg(&arg, &mut argv)? => (),
+            _ if query.is_none() => query = Some(arg),
+            _ => anyhow::bail!("unexpected argument {:?}", arg),
+        }
+    }
+
+    let query = query.context("graphql query required, see --help")?;
+
+    let read_stdin = || -> anyhow::Result<String> {
         use std::io::Read;
 
         let mut inp = String::new();
 
         std::io::stdin().lock().read_to_string(&mut inp)?;
 
+        Ok(inp)
+    };
+
+    let mut schema = schema(&options).data(AllowFiles);
+
+    /* stdin is either the document or the variables, not both; and --var is instead of
+     * variables on stdin */
+    let vars = if stdin_html {
+        schema = schema.data(Stdin(read_stdin()?));
+        serde_json::Value::Object(vars)
+    } else if !vars.is_empty() {
+        serde_json::Value::Object(vars)
+    } else {
+        let inp = read_stdin()?;
         if inp.is_empty() {
             serde_json::Value::Null
         } else {
@@ -153,32 +949,39 @@ fn main() -> anyhow::Result<()> {
     };
 
     use async_graphql::*;
-    let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
-    let req = Request::new(query).variables(Variables::from_json(vars));
+    let schema = schema.finish();
+    let mut req = Request::new(query).variables(Variables::from_json(vars));
+    if let Some(operation) = operation {
+        req = req.operation_name(operation);
+    }
     let res = extreme::run(schema.execute(req));
-    let s = serde_json::to_string(&res.data)?;
-    println!("{}", s);
 
+    /* errors first, they're likely why there's nothing to render */
     for err in res.errors.iter() {
         eprintln!("{}", err);
     }
 
+    let s = output.render(&serde_json::to_value(&res.data)?, path.as_deref())?;
+    println!("{}", s);
+
     Ok(())
 }
 
-fn walk<'a>(node: nipper::Node<'a>) -> impl Iterator<Item = nipper::Node<'a>> {
-    let mut stack = vec![node];
-
-    std::iter::from_fn(move || {
-        let next = stack.pop()?;
+/// `name=value` for a string, `name:=json` for anything else.
+fn parse_var(var: &str) -> anyhow::Result<(String, serde_json::Value)> {
+    let (name, value) = var
+        .split_once('=')
+        .with_context(|| format!("--var {:?} isn't NAME=VALUE", var))?;
 
-        /* push children to stack in reverse order */
-        let mut child = next.last_child();
-        while let Some(some) = child {
-            child = some.prev_sibling();
-            stack.push(some);
+    match name.strip_suffix(':') {
+        Some(name) => {
+            let value = serde_json::from_str(value)
+                .with_context(|| format!("--var {}'s value isn't JSON", name))?;
+            Ok((name.to_string(), value))
         }
-
-        Some(next)
-    })
+        None => Ok((
+            name.to_string(),
+            serde_json::Value::String(value.to_string()),
+        )),
+    }
 }
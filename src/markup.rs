@@ -0,0 +1,35 @@
+use nipper::Document;
+use xml5ever::tendril::TendrilSink;
+
+/// How a document is read into nodes.
+#[derive(async_graphql::Enum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Parser {
+    /// like a browser reads a web page
+    #[default]
+    Html,
+    /// keeps namespaces, CDATA and self-closing tags as they are; for feeds and the like
+    Xml,
+}
+
+impl Parser {
+    /// XML for media types like text/xml and application/rss+xml, but not XHTML; that's
+    /// better read as HTML.
+    pub fn for_content_type(content_type: Option<&str>) -> Parser {
+        let content_type = content_type.unwrap_or_default().to_ascii_lowercase();
+        let xml = content_type.ends_with("/xml") || content_type.ends_with("+xml");
+        if xml && content_type != "application/xhtml+xml" {
+            Parser::Xml
+        } else {
+            Parser::Html
+        }
+    }
+
+    pub fn parse(self, text: &str) -> Document {
+        match self {
+            Parser::Html => Document::from(text),
+            Parser::Xml => {
+                xml5ever::driver::parse_document(Document::default(), Default::default()).one(text)
+            }
+        }
+    }
+}
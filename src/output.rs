@@ -0,0 +1,516 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use serde_json::{Map, Value};
+use std::fmt::Write;
+
+use crate::{coerce, fnv};
+
+/// How the command line prints what a query returns.
+///
+/// All but the JSON formats are made from a list of rows. That's the list at the `path`
+/// given to render(), otherwise the first list of objects in the result.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Output {
+    #[default]
+    Json,
+    JsonPretty,
+    /// a row of JSON per line
+    Ndjson,
+    /// rows with nested objects flattened into columns named like `title.href`
+    Csv,
+    /// like csv, with tabs, newlines and backslashes escaped with backslashes
+    Tsv,
+    /// rows as feed entries; see feed()
+    Atom,
+    Rss,
+}
+
+impl std::str::FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Output> {
+        match s {
+            "json" => Ok(Output::Json),
+            "json-pretty" => Ok(Output::JsonPretty),
+            "ndjson" => Ok(Output::Ndjson),
+            "csv" => Ok(Output::Csv),
+            "tsv" => Ok(Output::Tsv),
+            "atom" => Ok(Output::Atom),
+            "rss" => Ok(Output::Rss),
+            _ => anyhow::bail!(
+                "unknown format {:?}, expected json, json-pretty, ndjson, csv, tsv, atom or rss",
+                s
+            ),
+        }
+    }
+}
+
+impl Output {
+    /// `path` is dotted field names, like `get.select`, leading to what's printed. Lists
+    /// along the way are gone through item by item.
+    pub fn render(self, data: &Value, path: Option<&str>) -> anyhow::Result<String> {
+        let (parent, rows) = match path {
+            Some(path) => at_path(data, path)
+                .ok_or_else(|| anyhow::anyhow!("the result has nothing at {:?}", path))?,
+            None => match self {
+                Output::Json | Output::JsonPretty => return json(self, data),
+                _ => first_list(data, None)
+                    .map(|(parent, list)| (parent, list.iter().collect()))
+                    .ok_or_else(|| anyhow::anyhow!("the result has no list of objects"))?,
+            },
+        };
+
+        match self {
+            Output::Json | Output::JsonPretty => {
+                json(self, &Value::Array(rows.into_iter().cloned().collect()))
+            }
+            Output::Ndjson => Ok(rows
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n")),
+            Output::Csv => Ok(table(&rows, csv_field, ",")),
+            Output::Tsv => Ok(table(&rows, tsv_field, "\t")),
+            Output::Atom => Ok(atom(&feed(parent, &rows))),
+            Output::Rss => Ok(rss(&feed(parent, &rows))),
+        }
+    }
+}
+
+fn json(output: Output, value: &Value) -> anyhow::Result<String> {
+    Ok(match output {
+        Output::JsonPretty => serde_json::to_string_pretty(value)?,
+        _ => serde_json::to_string(value)?,
+    })
+}
+
+/// What's at the path, with lists flattened into it, and the object it was in if there
+/// was only one. None if nothing along the way has the next name; an empty list just
+/// means no rows.
+fn at_path<'v>(
+    data: &'v Value,
+    path: &str,
+) -> Option<(Option<&'v Map<String, Value>>, Vec<&'v Value>)> {
+    let mut found = vec![(None, data)];
+
+    for name in path.split('.').filter(|name| !name.is_empty()) {
+        let values = found
+            .into_iter()
+            .flat_map(|(_, value)| items(value))
+            .collect::<Vec<_>>();
+        let searched = !values.is_empty();
+
+        found = values
+            .into_iter()
+            .filter_map(|value| {
+                let object = value.as_object()?;
+                Some((Some(object), object.get(name)?))
+            })
+            .collect();
+
+        if searched && found.is_empty() {
+            return None;
+        }
+    }
+
+    let parent = match found.as_slice() {
+        [(parent, _)] => *parent,
+        _ => None,
+    };
+    let rows = found
+        .into_iter()
+        .flat_map(|(_, value)| items(value))
+        .collect();
+    Some((parent, rows))
+}
+
+/// The items of a list, or just the value if it isn't one.
+fn items(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        value => vec![value],
+    }
+}
+
+/// A header row naming every column any row has, then the rows.
+fn table(rows: &[&Value], field: fn(&str) -> String, separator: &str) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            let mut columns = vec![];
+            flatten(String::new(), row, &mut columns);
+            columns
+        })
+        .collect::<Vec<_>>();
+
+    let mut names: Vec<&str> = vec![];
+    for (name, _) in rows.iter().flatten() {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+
+    let mut out = String::new();
+    let line = |fields: Vec<&str>| {
+        fields
+            .into_iter()
+            .map(field)
+            .collect::<Vec<_>>()
+            .join(separator)
+    };
+
+    out.push_str(&line(names.clone()));
+    for row in &rows {
+        out.push('\n');
+        out.push_str(&line(
+            names
+                .iter()
+                .map(|name| {
+                    row.iter()
+                        .find(|(column, _)| column == name)
+                        .map_or("", |(_, value)| value.as_str())
+                })
+                .collect(),
+        ));
+    }
+    out
+}
+
+/// Nested objects become columns named by their path. Lists are kept as JSON, null is
+/// an empty string.
+fn flatten(prefix: String, value: &Value, columns: &mut Vec<(String, String)>) {
+    if let Value::Object(map) = value {
+        if !map.is_empty() {
+            for (key, value) in map {
+                let column = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(column, value, columns);
+            }
+            return;
+        }
+    }
+
+    let column = if prefix.is_empty() {
+        "value".to_string()
+    } else {
+        prefix
+    };
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    columns.push((column, text));
+}
+
+/// Quoted if it has to be, per RFC 4180.
+fn csv_field(s: &str) -> String {
+    if s.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn tsv_field(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+struct Feed {
+    title: String,
+    author: String,
+    link: Option<String>,
+    id: String,
+    updated: DateTime<FixedOffset>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    title: String,
+    link: Option<String>,
+    id: String,
+    updated: Option<DateTime<FixedOffset>>,
+    content: Option<String>,
+}
+
+const DEFAULT_ID: &str = concat!("urn:", env!("CARGO_PKG_NAME"));
+
+/// The rows' `title`, `link`, `updated`, `id` and `content` fields are used for entries,
+/// so those are what to alias fields as. The feed's own title, author, link and id come
+/// from fields with those names next to the list, if there are any.
+///
+/// Rows without an id get one made from their title and content, so an entry keeps its
+/// id while the list around it changes.
+///
+/// The feed is updated at the `updated` next to the list, otherwise at its newest entry,
+/// otherwise at the Unix epoch; never the time it's made, so the same result always gives
+/// the same feed. Atom entries without a date of their own get the feed's.
+fn feed(parent: Option<&Map<String, Value>>, rows: &[&Value]) -> Feed {
+    let field = |name: &str| parent.and_then(|parent| parent.get(name)).and_then(scalar);
+
+    let now = Utc::now();
+    let entries = rows
+        .iter()
+        .filter_map(|row| row.as_object())
+        .map(|entry| {
+            let field = |name: &str| entry.get(name).and_then(scalar);
+            let title = field("title").unwrap_or_default();
+            let link = field("link");
+            let content = field("content");
+            Entry {
+                id: field("id").or_else(|| link.clone()).unwrap_or_else(|| {
+                    let content = content.as_deref().unwrap_or_default();
+                    format!("{}:{:016x}", DEFAULT_ID, fnv::hash(&[&title, content]))
+                }),
+                title,
+                link,
+                updated: field("updated")
+                    .and_then(|updated| coerce::datetime(&updated, None, now).ok()),
+                content,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let link = field("link");
+    Feed {
+        title: field("title").unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string()),
+        author: field("author").unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string()),
+        id: field("id")
+            .or_else(|| link.clone())
+            .or_else(|| entries.first().and_then(|entry| entry.link.clone()))
+            .unwrap_or_else(|| DEFAULT_ID.to_string()),
+        updated: field("updated")
+            .and_then(|updated| coerce::datetime(&updated, None, now).ok())
+            .or_else(|| entries.iter().filter_map(|entry| entry.updated).max())
+            .unwrap_or_else(|| DateTime::<Utc>::default().fixed_offset()),
+        link,
+        entries,
+    }
+}
+
+/// The first list of objects, depth first, and the object it's in.
+fn first_list<'v>(
+    value: &'v Value,
+    parent: Option<&'v Map<String, Value>>,
+) -> Option<(Option<&'v Map<String, Value>>, &'v Vec<Value>)> {
+    match value {
+        Value::Array(items) if items.iter().any(Value::is_object) => Some((parent, items)),
+        Value::Array(items) => items.iter().find_map(|item| first_list(item, parent)),
+        Value::Object(map) => map.values().find_map(|value| first_list(value, Some(map))),
+        _ => None,
+    }
+}
+
+/// Strings, numbers and booleans as strings. An object with one field, like what
+/// `title: querySelector(select: "h2") { text }` gives, is taken to be that field.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Object(map) if map.len() == 1 => map.values().next().and_then(scalar),
+        _ => None,
+    }
+}
+
+/// Entries with neither a link nor content are left out; Atom has no way to say what
+/// they're about.
+fn atom(feed: &Feed) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(out, "  <title>{}</title>", escape(&feed.title));
+    if let Some(link) = &feed.link {
+        let _ = writeln!(out, "  <link href=\"{}\"/>", escape(link));
+    }
+    let _ = writeln!(out, "  <id>{}</id>", escape(&feed.id));
+    let _ = writeln!(out, "  <updated>{}</updated>", feed.updated.to_rfc3339());
+    let _ = writeln!(
+        out,
+        "  <author><name>{}</name></author>",
+        escape(&feed.author)
+    );
+    let _ = writeln!(out, "  <generator>{}</generator>", env!("CARGO_PKG_NAME"));
+
+    for entry in &feed.entries {
+        if entry.link.is_none() && entry.content.is_none() {
+            continue;
+        }
+
+        out.push_str("  <entry>\n");
+        let _ = writeln!(out, "    <title>{}</title>", escape(&entry.title));
+        if let Some(link) = &entry.link {
+            let _ = writeln!(out, "    <link href=\"{}\"/>", escape(link));
+        }
+        let _ = writeln!(out, "    <id>{}</id>", escape(&entry.id));
+        let updated = entry.updated.unwrap_or(feed.updated);
+        let _ = writeln!(out, "    <updated>{}</updated>", updated.to_rfc3339());
+        if let Some(content) = &entry.content {
+            let _ = writeln!(
+                out,
+                "    <content type=\"html\">{}</content>",
+                escape(content)
+            );
+        }
+        out.push_str("  </entry>\n");
+    }
+
+    out.push_str("</feed>");
+    out
+}
+
+fn rss(feed: &Feed) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<rss version=\"2.0\">\n<channel>\n");
+    let _ = writeln!(out, "  <title>{}</title>", escape(&feed.title));
+    let _ = writeln!(
+        out,
+        "  <link>{}</link>",
+        escape(feed.link.as_deref().unwrap_or(&feed.id))
+    );
+    let _ = writeln!(out, "  <description>{}</description>", escape(&feed.title));
+    let _ = writeln!(out, "  <generator>{}</generator>", env!("CARGO_PKG_NAME"));
+
+    for entry in &feed.entries {
+        out.push_str("  <item>\n");
+        let _ = writeln!(out, "    <title>{}</title>", escape(&entry.title));
+        if let Some(link) = &entry.link {
+            let _ = writeln!(out, "    <link>{}</link>", escape(link));
+        }
+        let permalink = entry.link.as_deref() == Some(entry.id.as_str());
+        let _ = writeln!(
+            out,
+            "    <guid isPermaLink=\"{}\">{}</guid>",
+            permalink,
+            escape(&entry.id)
+        );
+        /* optional in RSS, so better left out than made up */
+        if let Some(updated) = entry.updated {
+            let _ = writeln!(out, "    <pubDate>{}</pubDate>", updated.to_rfc2822());
+        }
+        if let Some(content) = &entry.content {
+            let _ = writeln!(out, "    <description>{}</description>", escape(content));
+        }
+        out.push_str("  </item>\n");
+    }
+
+    out.push_str("</channel>\n</rss>");
+    out
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            /* not allowed in XML at all */
+            '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}' => (),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn tsv_fields() {
+        assert_eq!(tsv_field("plain, \"quoted\""), "plain, \"quoted\"");
+        assert_eq!(tsv_field("a\tb"), "a\\tb");
+        assert_eq!(tsv_field("two\nlines\r"), "two\\nlines\\r");
+        assert_eq!(tsv_field("back\\slash\\t"), "back\\\\slash\\\\t");
+    }
+
+    #[test]
+    fn flattened() {
+        let row = json!({
+            "title": {"href": "/fish", "text": "Fish"},
+            "tags": [1, 2],
+            "none": null,
+            "empty": {},
+            "deep": {"a": {"b": true}},
+        });
+        let mut columns = vec![];
+        flatten(String::new(), &row, &mut columns);
+
+        let columns = columns
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            [
+                ("title.href", "/fish"),
+                ("title.text", "Fish"),
+                ("tags", "[1,2]"),
+                ("none", ""),
+                ("empty", "{}"),
+                ("deep.a.b", "true"),
+            ]
+        );
+
+        let mut columns = vec![];
+        flatten(String::new(), &json!("alone"), &mut columns);
+        assert_eq!(columns, [("value".to_string(), "alone".to_string())]);
+    }
+
+    #[test]
+    fn paths() {
+        let data = json!({
+            "get": {
+                "title": "Fish",
+                "select": [{"name": "Cod"}, {"name": "Salmon"}],
+                "none": [],
+                "nothing": null,
+            }
+        });
+
+        let (parent, rows) = at_path(&data, "get.select").unwrap();
+        assert_eq!(parent.and_then(|p| p.get("title")), Some(&json!("Fish")));
+        assert_eq!(rows, [&json!({"name": "Cod"}), &json!({"name": "Salmon"})]);
+
+        /* lists along the way are gone through item by item */
+        let (_, rows) = at_path(&data, "get.select.name").unwrap();
+        assert_eq!(rows, [&json!("Cod"), &json!("Salmon")]);
+
+        assert!(at_path(&data, "get.missing").is_none());
+        assert!(at_path(&data, "missing.select").is_none());
+
+        /* an empty list is no rows, not nothing, even with more path after it */
+        let (_, rows) = at_path(&data, "get.none").unwrap();
+        assert!(rows.is_empty());
+        let (_, rows) = at_path(&data, "get.none.name").unwrap();
+        assert!(rows.is_empty());
+
+        /* null is a value, but doesn't have anything in it */
+        let (_, rows) = at_path(&data, "get.nothing").unwrap();
+        assert_eq!(rows, [&Value::Null]);
+        assert!(at_path(&data, "get.nothing.name").is_none());
+
+        let (parent, rows) = at_path(&data, "").unwrap();
+        assert!(parent.is_none());
+        assert_eq!(rows, [&data]);
+    }
+}
@@ -0,0 +1,109 @@
+use async_graphql::{InputValueError, Value};
+
+/// A regular expression, compiled when the query is parsed so a bad one is reported
+/// where it's written.
+pub struct Pattern(pub regex::Regex);
+
+#[async_graphql::Scalar(name = "Regex")]
+impl async_graphql::ScalarType for Pattern {
+    fn parse(value: Value) -> Result<Self, InputValueError<Self>> {
+        if let Value::String(s) = value {
+            regex::Regex::new(&s)
+                .map_err(InputValueError::custom)
+                .map(Pattern)
+        } else {
+            Err(InputValueError::custom(
+                "expected regular expression string",
+            ))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.as_str().to_string())
+    }
+}
+
+/// A capture group by its index, where 0 is the whole match, or by its name.
+pub enum Group {
+    Index(usize),
+    Name(String),
+}
+
+#[async_graphql::Scalar]
+impl async_graphql::ScalarType for Group {
+    fn parse(value: Value) -> Result<Self, InputValueError<Self>> {
+        match value {
+            Value::Number(n) => n
+                .as_u64()
+                .map(|n| Group::Index(n as usize))
+                .ok_or_else(|| InputValueError::custom("expected a non-negative group index")),
+            Value::String(s) => Ok(Group::Name(s)),
+            _ => Err(InputValueError::custom("expected group index or name")),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Group::Index(n) => Value::Number((*n as u64).into()),
+            Group::Name(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group::Index(0)
+    }
+}
+
+impl Group {
+    pub fn get<'t>(&self, captures: &regex::Captures<'t>) -> Option<regex::Match<'t>> {
+        match self {
+            Group::Index(n) => captures.get(*n),
+            Group::Name(s) => captures.name(s),
+        }
+    }
+}
+
+/// One match of a Regex.
+pub struct Captures {
+    groups: Vec<Option<String>>,
+    names: Vec<Option<String>>,
+}
+
+impl Captures {
+    pub fn new(regex: &regex::Regex, captures: &regex::Captures) -> Self {
+        Captures {
+            groups: captures
+                .iter()
+                .map(|m| m.map(|m| m.as_str().to_string()))
+                .collect(),
+            names: regex.capture_names().map(|n| n.map(String::from)).collect(),
+        }
+    }
+}
+
+#[async_graphql::Object]
+impl Captures {
+    /// The whole match.
+    async fn text(&self) -> &str {
+        self.groups[0].as_deref().unwrap_or_default()
+    }
+
+    /// Every group, starting with the whole match; null for those that didn't match
+    /// anything.
+    async fn groups(&self) -> Vec<Option<String>> {
+        self.groups.clone()
+    }
+
+    async fn group(&self, group: Group) -> Option<&str> {
+        let index = match group {
+            Group::Index(n) => n,
+            Group::Name(name) => self
+                .names
+                .iter()
+                .position(|n| n.as_deref() == Some(&name))?,
+        };
+        self.groups.get(index)?.as_deref()
+    }
+}
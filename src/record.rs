@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::cache::{key, read_json, write_json};
+use crate::fetch::{Request, Response};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// fetch as usual and save every response
+    Record,
+    /// answer every request from what was saved, never the network
+    Replay,
+}
+
+/// Saves responses to a directory and plays them back later, so a query can be run in CI
+/// without the sites it scrapes.
+///
+/// Unlike the Cache this keeps every response, whatever its method or status, and doesn't
+/// care about freshness.
+pub struct Recorder {
+    dir: PathBuf,
+    mode: Mode,
+}
+
+/// What goes in each file. The request is only there for whoever is reading the files.
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    method: String,
+    url: String,
+    response: Response,
+}
+
+impl Recorder {
+    pub fn new(dir: PathBuf, mode: Mode) -> Self {
+        Recorder { dir, mode }
+    }
+
+    pub fn fetch<F>(&self, request: &Request, send: F) -> anyhow::Result<Response>
+    where
+        F: FnOnce(&Request) -> anyhow::Result<Response>,
+    {
+        let path = self.dir.join(format!("{}.json", key(request)));
+
+        match self.mode {
+            Mode::Replay => read_json::<Recording>(&path)
+                .map(|recording| recording.response)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no recording of {} {} in {}",
+                        request.method.as_str(),
+                        request.url,
+                        self.dir.display()
+                    )
+                }),
+            Mode::Record => {
+                let response = send(request)?;
+                let recording = Recording {
+                    method: request.method.as_str().to_string(),
+                    url: request.url.clone(),
+                    response,
+                };
+                write_json(&self.dir, &path, &recording)?;
+                Ok(recording.response)
+            }
+        }
+    }
+}
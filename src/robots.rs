@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::fetch::Response;
+
+/// How long a robots.txt is used for before it's downloaded again; RFC 9309 says no more
+/// than a day.
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long to assume everything is disallowed after failing to get a robots.txt.
+const ROBOTS_RETRY: Duration = Duration::from_secs(60);
+
+/// The error for a request that robots.txt says we shouldn't make.
+#[derive(Debug)]
+pub struct Disallowed(pub String);
+
+impl std::fmt::Display for Disallowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "robots.txt disallows {}", self.0)
+    }
+}
+
+impl std::error::Error for Disallowed {}
+
+/// Remembers each origin's robots.txt and checks URLs against it. Fetcher does the
+/// downloading, so robots.txt is fetched like any other request.
+#[derive(Default)]
+pub struct Robots {
+    /// by robots.txt URL
+    origins: Mutex<HashMap<String, Known>>,
+}
+
+struct Known {
+    robots: RobotsTxt,
+    until: Instant,
+}
+
+enum RobotsTxt {
+    Text(String),
+    AllowAll,
+    DisallowAll,
+}
+
+impl Robots {
+    /// The robots.txt to download before `url` can be checked, if we don't have it or it's
+    /// expired.
+    pub fn wanted(&self, url: &str) -> anyhow::Result<Option<String>> {
+        let url = Url::parse(url)?;
+
+        if url.path() == "/robots.txt" {
+            return Ok(None);
+        }
+
+        let robots_url = robots_url(&url);
+        let origins = self.origins.lock().unwrap();
+        Ok(match origins.get(&robots_url) {
+            Some(known) if known.until > Instant::now() => None,
+            _ => Some(robots_url),
+        })
+    }
+
+    /// Remembers what came of fetching `robots_url`, a URL from `wanted`.
+    ///
+    /// Per RFC 9309; a 4xx means there are no rules, anything else that isn't a robots.txt
+    /// means we can't tell so assume everything is disallowed, but not for long.
+    pub fn store(&self, robots_url: String, response: anyhow::Result<Response>) {
+        let (robots, ttl) = match response {
+            Ok(res) if (200..300).contains(&res.status) => (RobotsTxt::Text(res.body), ROBOTS_TTL),
+            Ok(res) if (400..500).contains(&res.status) => (RobotsTxt::AllowAll, ROBOTS_TTL),
+            _ => (RobotsTxt::DisallowAll, ROBOTS_RETRY),
+        };
+
+        let until = Instant::now() + ttl;
+        self.origins
+            .lock()
+            .unwrap()
+            .insert(robots_url, Known { robots, until });
+    }
+
+    /// Fails with Disallowed if the stored robots.txt doesn't allow `url`, or if there
+    /// isn't one.
+    pub fn check(&self, url: &str, user_agent: &str) -> anyhow::Result<()> {
+        let url = Url::parse(url)?;
+
+        if url.path() == "/robots.txt" {
+            return Ok(());
+        }
+
+        let origins = self.origins.lock().unwrap();
+        let allowed = match origins.get(&robots_url(&url)).map(|known| &known.robots) {
+            Some(RobotsTxt::Text(text)) => Rules::parse(text, user_agent).allows(&path_of(&url)),
+            Some(RobotsTxt::AllowAll) => true,
+            Some(RobotsTxt::DisallowAll) | None => false,
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Disallowed(url.into()).into())
+        }
+    }
+}
+
+fn robots_url(url: &Url) -> String {
+    format!("{}/robots.txt", url.origin().ascii_serialization())
+}
+
+fn path_of(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// The allow and disallow lines that apply to one user agent.
+#[derive(Debug, Default)]
+struct Rules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Rules {
+    /// Takes the rules from every group naming our product token, or from the `*` groups
+    /// if none do.
+    fn parse(text: &str, user_agent: &str) -> Rules {
+        let token = user_agent
+            .split(|c: char| c == '/' || c.is_whitespace())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let mut ours = vec![];
+        let mut everyone = vec![];
+        let mut named = false;
+
+        /* user agents of the group we're in and whether we've seen its rules yet */
+        let mut agents: Vec<String> = vec![];
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                    named |= value.eq_ignore_ascii_case(&token);
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+
+                    /* an empty disallow doesn't disallow anything */
+                    if value.is_empty() {
+                        continue;
+                    }
+
+                    let rule = || Rule {
+                        allow: key == "allow",
+                        pattern: value.to_string(),
+                    };
+
+                    if agents.iter().any(|agent| *agent == token) {
+                        ours.push(rule());
+                    }
+                    if agents.iter().any(|agent| agent == "*") {
+                        everyone.push(rule());
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let rules = if named { ours } else { everyone };
+        Rules { rules }
+    }
+
+    /// The longest matching pattern wins, allow wins ties, and no match is allowed.
+    fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map_or(true, |rule| rule.allow)
+    }
+}
+
+/// `*` matches any run of characters and a trailing `$` anchors the end, otherwise the
+/// pattern is a prefix.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(pattern_matches("/fish", "/fish.html"));
+        assert!(pattern_matches("/fish", "/fish/salmon.html"));
+        assert!(!pattern_matches("/fish", "/Fish.asp"));
+        assert!(!pattern_matches("/fish", "/catfish"));
+
+        assert!(pattern_matches("/fish*.php", "/fish/salmon.php"));
+        assert!(pattern_matches(
+            "/fish*.php",
+            "/fishheads/catfish.php?parameters"
+        ));
+        assert!(!pattern_matches("/fish*.php", "/Fish.PHP"));
+
+        assert!(pattern_matches("/*.php$", "/filename.php"));
+        assert!(pattern_matches("/*.php$", "/folder/filename.php"));
+        assert!(!pattern_matches("/*.php$", "/filename.php?parameters"));
+        assert!(!pattern_matches("/*.php$", "/filename.php5"));
+
+        assert!(pattern_matches("/fish$", "/fish"));
+        assert!(!pattern_matches("/fish$", "/fishheads"));
+
+        assert!(pattern_matches("/*", "/"));
+        assert!(pattern_matches("*", "/anything"));
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let rules = Rules::parse("User-agent: *\nAllow: /p\nDisallow: /\n", "bot/1.0");
+        assert!(rules.allows("/page"));
+        assert!(!rules.allows("/other"));
+
+        let rules = Rules::parse("User-agent: *\nAllow: /page\nDisallow: /*.htm\n", "bot/1.0");
+        assert!(!rules.allows("/page.htm"));
+        assert!(rules.allows("/page"));
+    }
+
+    #[test]
+    fn allow_wins_ties() {
+        let rules = Rules::parse(
+            "User-agent: *\nDisallow: /folder\nAllow: /folder\n",
+            "bot/1.0",
+        );
+        assert!(rules.allows("/folder/page"));
+    }
+
+    #[test]
+    fn groups() {
+        let text = "\
+User-agent: *
+Disallow: /
+
+User-agent: other
+User-agent: Bot
+Disallow: /private # not for us
+Disallow:
+";
+        let rules = Rules::parse(text, "bot/1.0 (+https://example.com)");
+        assert!(rules.allows("/"));
+        assert!(!rules.allows("/private/page"));
+
+        let rules = Rules::parse(text, "someone-else");
+        assert!(!rules.allows("/"));
+
+        assert!(Rules::parse("", "bot").allows("/anything"));
+    }
+
+    #[test]
+    fn stored() {
+        let robots = Robots::default();
+        let wanted = robots.wanted("https://example.com/page").unwrap();
+        assert_eq!(wanted.as_deref(), Some("https://example.com/robots.txt"));
+        assert!(robots
+            .wanted("https://example.com/robots.txt")
+            .unwrap()
+            .is_none());
+
+        let response = |status| Response {
+            status,
+            body: "User-agent: *\nDisallow: /private\n".to_string(),
+            ..Default::default()
+        };
+
+        robots.store(wanted.unwrap(), Ok(response(200)));
+        assert!(robots
+            .wanted("https://example.com/other")
+            .unwrap()
+            .is_none());
+        assert!(robots.check("https://example.com/page", "bot").is_ok());
+        assert!(robots.check("https://example.com/private", "bot").is_err());
+
+        robots.store("https://a.example/robots.txt".into(), Ok(response(404)));
+        assert!(robots.check("https://a.example/private", "bot").is_ok());
+
+        robots.store("https://b.example/robots.txt".into(), Ok(response(503)));
+        assert!(robots.check("https://b.example/page", "bot").is_err());
+
+        robots.store(
+            "https://c.example/robots.txt".into(),
+            Err(anyhow::anyhow!("timed out")),
+        );
+        assert!(robots.check("https://c.example/page", "bot").is_err());
+        assert!(robots.check("https://d.example/page", "bot").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::Context;
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{fetch, Schema};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+pub fn main(
+    mut options: fetch::Options,
+    mut argv: impl Iterator<Item = String>,
+) -> anyhow::Result<()> {
+    let mut listen = DEFAULT_LISTEN.to_string();
+    let mut allow_files = false;
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", crate::USAGE);
+                return Ok(());
+            }
+            "--listen" => listen = argv.next().context("--listen requires an address")?,
+            /* off by default, anyone who can reach the server could read our files */
+            "--allow-files" => allow_files = true,
+            _ if options.parse_arg(&arg, &mut argv)? => (),
+            _ => match arg.strip_prefix("--listen=") {
+                Some(addr) => listen = addr.to_string(),
+                None => anyhow::bail!("unexpected argument {:?}", arg),
+            },
+        }
+    }
+
+    let mut schema = crate::schema(&options);
+    if allow_files {
+        schema = schema.data(crate::AllowFiles);
+    }
+
+    serve(schema.finish(), &listen)
+}
+
+pub fn serve(schema: Schema, listen: &str) -> anyhow::Result<()> {
+    let server = Server::http(listen)
+        .map_err(|e| anyhow::anyhow!(e))
+        .with_context(|| format!("listen on {}", listen))?;
+    let server = Arc::new(server);
+
+    eprintln!("listening on http://{}/graphql", server.server_addr());
+
+    /* each worker blocks on its own request, so a slow scrape only holds up one of them */
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+
+    let handles = (0..workers)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let schema = schema.clone();
+            std::thread::spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => handle(&schema, request),
+                    Err(err) => eprintln!("{}", err),
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    Ok(())
+}
+
+fn handle(schema: &Schema, mut request: Request) {
+    let response = match graphql_request(&mut request) {
+        Ok(req) => {
+            let res = extreme::run(schema.execute(req));
+            match serde_json::to_string(&res) {
+                Ok(body) => json(200, body),
+                Err(err) => text(500, err.to_string()),
+            }
+        }
+        Err(reply) => reply,
+    };
+
+    if let Err(err) = request.respond(response) {
+        eprintln!("{}", err);
+    }
+}
+
+type Reply = Response<std::io::Cursor<Vec<u8>>>;
+
+/// Reads an async_graphql::Request out of a GraphQL-over-HTTP request; GET takes `query`,
+/// `variables` and `operationName` from the query string and POST takes them from a JSON
+/// body.
+fn graphql_request(request: &mut Request) -> Result<async_graphql::Request, Reply> {
+    let (path, query_string) = request.url().split_once('?').unwrap_or((request.url(), ""));
+
+    if path != "/graphql" {
+        return Err(text(404, "not found"));
+    }
+
+    match request.method() {
+        Method::Get => async_graphql::http::parse_query_string(query_string)
+            .map_err(|err| text(400, err.to_string())),
+        Method::Post => {
+            let content_type = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("Content-Type"))
+                .map(|h| h.value.as_str().to_owned())
+                .unwrap_or_default();
+
+            let mut body = String::new();
+            std::io::Read::read_to_string(request.as_reader(), &mut body)
+                .map_err(|err| text(400, err.to_string()))?;
+
+            if content_type.starts_with("application/graphql") {
+                Ok(async_graphql::Request::new(body))
+            } else {
+                serde_json::from_str(&body).map_err(|err| text(400, err.to_string()))
+            }
+        }
+        _ => Err(text(405, "method not allowed").with_header(header("Allow", "GET, POST"))),
+    }
+}
+
+fn json(status: u16, body: String) -> Reply {
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn text(status: u16, body: impl Into<String>) -> Reply {
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "text/plain; charset=utf-8"))
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("static header is valid")
+}
@@ -0,0 +1,150 @@
+use nipper::{MatchScope, Matcher, Matches};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::text::{self, TextOptions};
+use crate::tree::{self, resolve};
+
+/// Everything in `<script type="application/ld+json">`. A script holding an array gives
+/// each thing in it; scripts that aren't valid JSON are skipped.
+pub fn json_ld(root: nipper::Node) -> Vec<Value> {
+    let mut found = vec![];
+
+    for script in elements(root, "script[type]") {
+        let is_json_ld = script.attr("type").map_or(false, |t| {
+            let t = t.split(';').next().unwrap_or_default();
+            t.trim().eq_ignore_ascii_case("application/ld+json")
+        });
+        if !is_json_ld {
+            continue;
+        }
+
+        match serde_json::from_str(&script.text()) {
+            Ok(Value::Array(values)) => found.extend(values),
+            Ok(value) => found.push(value),
+            Err(_) => (),
+        }
+    }
+
+    found
+}
+
+/// Top level microdata items, shaped like the JSON in the HTML spec:
+/// `{"type": [...], "id": ..., "properties": {"name": [values]}}`. URL properties are
+/// resolved against `base`. `itemref` isn't followed.
+pub fn microdata(root: nipper::Node, base: Option<&Url>) -> Vec<Value> {
+    elements(root, "[itemscope]:not([itemprop])")
+        .into_iter()
+        .map(|node| item(node, base))
+        .collect()
+}
+
+fn item(node: nipper::Node, base: Option<&Url>) -> Value {
+    let mut item = Map::new();
+
+    if let Some(types) = node.attr("itemtype") {
+        let types = types
+            .split_ascii_whitespace()
+            .map(|t| Value::String(t.to_string()))
+            .collect();
+        item.insert("type".to_string(), Value::Array(types));
+    }
+    if let Some(id) = node.attr("itemid") {
+        item.insert("id".to_string(), Value::String(resolve(base, &id)));
+    }
+
+    let mut properties = Map::new();
+    let mut walk = tree::walk(node);
+    /* the item itself */
+    walk.next();
+
+    while let Some(node) = walk.next() {
+        /* a nested item's properties are its own */
+        if node.attr("itemscope").is_some() {
+            walk.skip_children();
+        }
+
+        if let Some(names) = node.attr("itemprop") {
+            let value = property_value(node, base);
+            for name in names.split_ascii_whitespace() {
+                if let Value::Array(values) = properties
+                    .entry(name.to_string())
+                    .or_insert_with(|| Value::Array(vec![]))
+                {
+                    values.push(value.clone());
+                }
+            }
+        }
+    }
+
+    item.insert("properties".to_string(), Value::Object(properties));
+    Value::Object(item)
+}
+
+fn property_value(node: nipper::Node, base: Option<&Url>) -> Value {
+    if node.attr("itemscope").is_some() {
+        return item(node, base);
+    }
+
+    let attr = |name: &str| node.attr(name).map(|value| value.to_string());
+    let url = |name: &str| attr(name).map(|value| resolve(base, &value));
+
+    let value = match tree::name(&node).as_str() {
+        "meta" => attr("content"),
+        "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => url("src"),
+        "a" | "area" | "link" => url("href"),
+        "object" => url("data"),
+        "data" | "meter" => attr("value"),
+        "time" => attr("datetime"),
+        _ => None,
+    };
+
+    let value = value.unwrap_or_else(|| text::text(node, &TextOptions::normalized()));
+    Value::String(value)
+}
+
+/// `<meta property>` tags, like OpenGraph's `og:title`, by property. A property that's
+/// there more than once, like `og:image` often is, gives an array.
+pub fn open_graph(root: nipper::Node) -> Map<String, Value> {
+    let mut found = Map::new();
+
+    for meta in elements(root, "meta[property][content]") {
+        let (property, content) = match (meta.attr("property"), meta.attr("content")) {
+            (Some(property), Some(content)) => (property.to_string(), content.to_string()),
+            _ => continue,
+        };
+
+        match found.get_mut(&property) {
+            None => {
+                found.insert(property, Value::String(content));
+            }
+            Some(Value::Array(values)) => values.push(Value::String(content)),
+            Some(value) => *value = Value::Array(vec![value.take(), Value::String(content)]),
+        }
+    }
+
+    found
+}
+
+/// The content of the first `<meta>` with this name or property; ignoring case.
+pub fn meta(root: nipper::Node, name: &str) -> Option<String> {
+    elements(root, "meta[content]")
+        .into_iter()
+        .find_map(|meta| {
+            let named = ["name", "property", "http-equiv"].iter().any(|attr| {
+                meta.attr(attr)
+                    .map_or(false, |value| value.trim().eq_ignore_ascii_case(name))
+            });
+            named
+                .then(|| meta.attr("content"))
+                .flatten()
+                .map(|content| content.to_string())
+        })
+}
+
+fn elements<'a>(root: nipper::Node<'a>, selector: &str) -> Vec<nipper::Node<'a>> {
+    match Matcher::new(selector) {
+        Ok(matcher) => Matches::from_one(root, matcher, MatchScope::IncludeNode).collect(),
+        Err(_) => vec![],
+    }
+}
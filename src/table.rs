@@ -0,0 +1,237 @@
+use async_graphql::Json;
+use serde_json::{Map, Value};
+
+use crate::text::{self, TextOptions};
+use crate::tree::{children, name};
+
+/// A `<table>` laid out on a grid, with a cell spanning several rows or columns copied
+/// into each of them.
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// The most rows or columns one cell may span; the same limits browsers have.
+const MAX_COLSPAN: usize = 1000;
+const MAX_ROWSPAN: usize = 65534;
+
+impl Table {
+    /// None unless `node` is a `<table>`.
+    ///
+    /// The header is the `<thead>` if there is one, otherwise the first row if it's all
+    /// `<th>`. Several header rows are joined column by column, so "Price" spanning
+    /// "Min" and "Max" gives "Price Min" and "Price Max".
+    pub fn new(node: nipper::Node) -> Option<Table> {
+        if name(&node) != "table" {
+            return None;
+        }
+
+        let options = TextOptions {
+            normalize: true,
+            trim: true,
+            separator: Some(" ".to_string()),
+        };
+
+        /* rows, and whether they're in a thead */
+        let mut trs = vec![];
+        for child in children(&node) {
+            match name(&child).as_str() {
+                "tr" => trs.push((child, false)),
+                section @ ("thead" | "tbody" | "tfoot") => trs.extend(
+                    children(&child)
+                        .filter(|tr| name(tr) == "tr")
+                        .map(|tr| (tr, section == "thead")),
+                ),
+                _ => (),
+            }
+        }
+
+        /* each slot has the text of the cell covering it and which cell that was */
+        let mut grid: Vec<Vec<Option<(usize, String)>>> = vec![vec![]; trs.len()];
+        let mut all_th = vec![];
+        let mut cells = 0;
+
+        for (r, (tr, _)) in trs.iter().enumerate() {
+            let mut col = 0;
+            let mut th = true;
+
+            for cell in children(tr) {
+                let cell_name = name(&cell);
+                if cell_name != "td" && cell_name != "th" {
+                    continue;
+                }
+                th &= cell_name == "th";
+
+                let span = |attr: &str, max: usize| {
+                    cell.attr(attr)
+                        .and_then(|n| n.trim().parse::<usize>().ok())
+                        .filter(|n| *n > 0)
+                        .unwrap_or(1)
+                        .min(max)
+                };
+                let colspan = span("colspan", MAX_COLSPAN);
+                let rowspan = span("rowspan", MAX_ROWSPAN).min(trs.len() - r);
+
+                while grid[r].get(col).map_or(false, Option::is_some) {
+                    col += 1;
+                }
+
+                let text = text::text(cell, &options);
+                for row in &mut grid[r..r + rowspan] {
+                    if row.len() < col + colspan {
+                        row.resize(col + colspan, None);
+                    }
+                    for slot in &mut row[col..col + colspan] {
+                        *slot = Some((cells, text.clone()));
+                    }
+                }
+                col += colspan;
+                cells += 1;
+            }
+
+            all_th.push(th && !grid[r].is_empty());
+        }
+
+        let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut grid {
+            row.resize(width, None);
+        }
+
+        let header_rows = match trs.iter().take_while(|(_, thead)| *thead).count() {
+            0 if all_th.first() == Some(&true) => 1,
+            n => n,
+        };
+
+        let mut headers = vec![String::new(); width];
+        let mut last_cell = vec![None; width];
+        for row in grid.drain(..header_rows) {
+            for ((header, last_cell), slot) in headers.iter_mut().zip(&mut last_cell).zip(row) {
+                let (cell, text) = match slot {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+                /* a cell spanning several header rows shouldn't be repeated */
+                if text.is_empty() || *last_cell == Some(cell) {
+                    continue;
+                }
+                *last_cell = Some(cell);
+                if !header.is_empty() {
+                    header.push(' ');
+                }
+                header.push_str(&text);
+            }
+        }
+
+        let rows = grid
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|slot| slot.map(|(_, text)| text).unwrap_or_default())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        Some(Table { headers, rows })
+    }
+}
+
+#[async_graphql::Object]
+impl Table {
+    /// The text of each column's header; empty strings if the table has no header.
+    async fn headers(&self) -> &Vec<String> {
+        &self.headers
+    }
+
+    /// The text of each cell in each row after the header. Every row is as long as the
+    /// widest one.
+    async fn rows(&self) -> &Vec<Vec<String>> {
+        &self.rows
+    }
+
+    /// Each row as an object keyed by header. Columns without a header are keyed by
+    /// their number, from 1, and a header used twice gets "_2" and so on after it.
+    async fn records(&self) -> Vec<Json<Map<String, Value>>> {
+        let mut keys: Vec<String> = vec![];
+        for (i, header) in self.headers.iter().enumerate() {
+            let key = match header.as_str() {
+                "" => (i + 1).to_string(),
+                header => header.to_string(),
+            };
+            let mut unique = key.clone();
+            let mut n = 1;
+            while keys.contains(&unique) {
+                n += 1;
+                unique = format!("{}_{}", key, n);
+            }
+            keys.push(unique);
+        }
+
+        self.rows
+            .iter()
+            .map(|row| {
+                Json(
+                    keys.iter()
+                        .cloned()
+                        .zip(row.iter().cloned().map(Value::String))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::walk;
+
+    fn table(html: &str) -> Table {
+        let document = nipper::Document::from(html);
+        let node = walk(document.root()).find(|node| name(node) == "table");
+        Table::new(node.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rowspan() {
+        let table = table(
+            "<table>
+                <tr><th>Name<th>Size
+                <tr><td rowspan=2>Salmon<td>Big
+                <tr><td>Small
+            </table>",
+        );
+        assert_eq!(table.headers, ["Name", "Size"]);
+        assert_eq!(table.rows, [["Salmon", "Big"], ["Salmon", "Small"]]);
+    }
+
+    #[test]
+    fn colspan() {
+        let table = table(
+            "<table>
+                <tr><td colspan=2>Both<td>Third
+                <tr><td>One<td>Two
+            </table>",
+        );
+        assert_eq!(table.headers, ["", "", ""]);
+        assert_eq!(table.rows, [["Both", "Both", "Third"], ["One", "Two", ""]]);
+    }
+
+    #[test]
+    fn header_rows() {
+        let table = table(
+            "<table>
+                <thead>
+                    <tr><th rowspan=2>Fish<th colspan=2>Price<th>Fish Price
+                    <tr><th>Min<th>Max<th>Price
+                </thead>
+                <tr><td>Cod<td>1<td>2<td>3
+            </table>",
+        );
+        /* the last header ends with "Price" but isn't the cell spanning down to it */
+        assert_eq!(
+            table.headers,
+            ["Fish", "Price Min", "Price Max", "Fish Price Price"]
+        );
+        assert_eq!(table.rows, [["Cod", "1", "2", "3"]]);
+    }
+}
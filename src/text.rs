@@ -0,0 +1,176 @@
+use crate::tree;
+
+/// How Node.text should tidy up the text it finds. The default leaves it exactly as it is
+/// in the document.
+#[derive(Clone, Debug, Default)]
+pub struct TextOptions {
+    /// collapse runs of whitespace into one space
+    pub normalize: bool,
+    /// strip whitespace from the start and end
+    pub trim: bool,
+    /// put this between block elements like `<p>` and `<li>`
+    pub separator: Option<String>,
+}
+
+impl TextOptions {
+    /// Roughly what a browser's innerText gives you.
+    pub fn inner_text() -> Self {
+        TextOptions {
+            normalize: true,
+            trim: true,
+            separator: Some("\n".to_string()),
+        }
+    }
+
+    /// All on one line with the whitespace tidied; for short things like labels.
+    pub fn normalized() -> Self {
+        TextOptions {
+            normalize: true,
+            trim: true,
+            separator: None,
+        }
+    }
+
+    fn is_verbatim(&self) -> bool {
+        !self.normalize && !self.trim && self.separator.is_none()
+    }
+}
+
+/// Elements that start on a new line.
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "caption",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// Elements whose text isn't text anyone reads.
+const SKIPPED: &[&str] = &["script", "style", "noscript", "template"];
+
+/// The text in and under `node`. Unless the options are all off, this leaves out the
+/// contents of `<script>`, `<style>` and the like.
+pub fn text(node: nipper::Node, options: &TextOptions) -> String {
+    let verbatim = options.is_verbatim();
+    let separate = options.separator.is_some();
+
+    /* the text between block boundaries */
+    let mut segments = vec![String::new()];
+    /* how deep each block we're in is */
+    let mut blocks: Vec<usize> = vec![];
+    let mut walk = tree::walk(node);
+
+    while let Some(node) = walk.next() {
+        /* a node no deeper than a block is after its end */
+        while blocks.last().map_or(false, |&depth| depth >= walk.depth()) {
+            blocks.pop();
+            segments.push(String::new());
+        }
+
+        if node.is_text() {
+            if let Some(segment) = segments.last_mut() {
+                segment.push_str(&node.text());
+            }
+            continue;
+        }
+
+        if node.is_element() && !verbatim {
+            let name = tree::name(&node);
+
+            if SKIPPED.contains(&name.as_str()) {
+                walk.skip_children();
+                continue;
+            }
+
+            if separate && BLOCKS.contains(&name.as_str()) {
+                segments.push(String::new());
+                blocks.push(walk.depth());
+            }
+        }
+    }
+
+    let mut segments = segments
+        .into_iter()
+        .map(|segment| {
+            if options.normalize {
+                collapse_whitespace(&segment)
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if separate {
+        /* whitespace next to a block boundary doesn't mean anything */
+        if options.normalize {
+            segments = segments
+                .into_iter()
+                .map(|segment| segment.trim().to_string())
+                .collect();
+        }
+        /* nor does whitespace on its own between them, normalized or not */
+        segments.retain(|segment| !segment.trim().is_empty());
+    }
+
+    let text = segments.join(options.separator.as_deref().unwrap_or_default());
+
+    if options.trim {
+        text.trim().to_string()
+    } else {
+        text
+    }
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_whitespace = false;
+
+    for c in s.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                out.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            out.push(c);
+            in_whitespace = false;
+        }
+    }
+
+    out
+}
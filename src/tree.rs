@@ -0,0 +1,68 @@
+use url::Url;
+
+/// `node` and everything under it, in document order.
+pub fn walk<'a>(node: nipper::Node<'a>) -> Walk<'a> {
+    Walk {
+        stack: vec![(node, 0)],
+        children: 0,
+        depth: 0,
+    }
+}
+
+pub struct Walk<'a> {
+    stack: Vec<(nipper::Node<'a>, usize)>,
+    /// how many children of the last node returned are on the stack
+    children: usize,
+    depth: usize,
+}
+
+impl Walk<'_> {
+    /// Don't go under the node last returned.
+    pub fn skip_children(&mut self) {
+        self.stack.truncate(self.stack.len() - self.children);
+        self.children = 0;
+    }
+
+    /// How far under the node the walk started at the node last returned is.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = nipper::Node<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+
+        /* push children to stack in reverse order */
+        let before = self.stack.len();
+        let mut child = node.last_child();
+        while let Some(some) = child {
+            child = some.prev_sibling();
+            self.stack.push((some, depth + 1));
+        }
+
+        self.children = self.stack.len() - before;
+        self.depth = depth;
+        Some(node)
+    }
+}
+
+/// The element's tag name, or "" for other nodes.
+pub fn name(node: &nipper::Node) -> String {
+    node.node_name()
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
+
+pub fn children<'a>(node: &nipper::Node<'a>) -> impl Iterator<Item = nipper::Node<'a>> {
+    std::iter::successors(node.first_child(), |node| node.next_sibling())
+}
+
+/// `value` as an absolute URL if it can be made into one, otherwise as it is.
+pub fn resolve(base: Option<&Url>, value: &str) -> String {
+    base.and_then(|base| base.join(value.trim()).ok())
+        .map(String::from)
+        .unwrap_or_else(|| value.to_string())
+}
@@ -0,0 +1,178 @@
+use async_graphql::{InputValueError, Value};
+use std::collections::HashMap;
+use sxd_document::{dom, Package};
+use sxd_xpath::{nodeset, Factory};
+
+/// An XPath 1.0 expression. It's checked when the query is parsed but compiled again each
+/// time it's evaluated; sxd_xpath's compiled expressions can't be shared between threads.
+pub struct XPath(String);
+
+#[async_graphql::Scalar(name = "XPath")]
+impl async_graphql::ScalarType for XPath {
+    fn parse(value: Value) -> Result<Self, InputValueError<Self>> {
+        if let Value::String(s) = value {
+            match Factory::new().build(&s) {
+                Ok(Some(_)) => Ok(XPath(s)),
+                Ok(None) => Err(InputValueError::custom("empty xpath")),
+                Err(err) => Err(InputValueError::custom(err)),
+            }
+        } else {
+            Err(InputValueError::custom("expected xpath string"))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
+
+/// Ids of the nodes the expression selects, in document order. Attributes don't have ids
+/// so they're left out.
+pub fn nodes(
+    document: &nipper::Document,
+    context: nipper::NodeId,
+    xpath: &XPath,
+) -> Result<Vec<nipper::NodeId>, String> {
+    evaluate(document, context, xpath, |value, ids| match value {
+        sxd_xpath::Value::Nodeset(nodes) => Ok(nodes
+            .document_order()
+            .iter()
+            .filter_map(|node| ids.get(node).copied())
+            .collect()),
+        value => Err(not_nodes(xpath, &value)),
+    })
+}
+
+/// The string-value of each node the expression selects, in document order.
+pub fn strings(
+    document: &nipper::Document,
+    context: nipper::NodeId,
+    xpath: &XPath,
+) -> Result<Vec<String>, String> {
+    evaluate(document, context, xpath, |value, _| match value {
+        sxd_xpath::Value::Nodeset(nodes) => Ok(nodes
+            .document_order()
+            .iter()
+            .map(|node| node.string_value())
+            .collect()),
+        value => Err(not_nodes(xpath, &value)),
+    })
+}
+
+/// The result as if passed to XPath's string().
+pub fn string(
+    document: &nipper::Document,
+    context: nipper::NodeId,
+    xpath: &XPath,
+) -> Result<String, String> {
+    evaluate(document, context, xpath, |value, _| Ok(value.string()))
+}
+
+/// The result as if passed to XPath's number().
+pub fn number(
+    document: &nipper::Document,
+    context: nipper::NodeId,
+    xpath: &XPath,
+) -> Result<f64, String> {
+    evaluate(document, context, xpath, |value, _| Ok(value.number()))
+}
+
+fn not_nodes(xpath: &XPath, value: &sxd_xpath::Value) -> String {
+    let kind = match value {
+        sxd_xpath::Value::Boolean(_) => "boolean",
+        sxd_xpath::Value::Number(_) => "number",
+        sxd_xpath::Value::String(_) => "string",
+        sxd_xpath::Value::Nodeset(_) => "node-set",
+    };
+    format!("{:?} gives a {}, not nodes", xpath.0, kind)
+}
+
+/// Copies the document into one sxd_xpath can evaluate against and hands `f` the result,
+/// along with what the copied nodes were in the original.
+fn evaluate<R, F>(
+    document: &nipper::Document,
+    context: nipper::NodeId,
+    xpath: &XPath,
+    f: F,
+) -> Result<R, String>
+where
+    F: for<'d> FnOnce(
+        sxd_xpath::Value<'d>,
+        &HashMap<nodeset::Node<'d>, nipper::NodeId>,
+    ) -> Result<R, String>,
+{
+    let compiled = Factory::new()
+        .build(&xpath.0)
+        .map_err(|err| err.to_string())?
+        .ok_or("empty xpath")?;
+
+    let package = Package::new();
+    let copied = copy(document, package.as_document(), context);
+    let value = compiled
+        .evaluate(&sxd_xpath::Context::new(), copied.context)
+        .map_err(|err| err.to_string())?;
+
+    f(value, &copied.ids)
+}
+
+struct Copied<'d> {
+    ids: HashMap<nodeset::Node<'d>, nipper::NodeId>,
+    /// the copy of the context node
+    context: nodeset::Node<'d>,
+}
+
+/// Elements, their attributes and text are copied. Comments and the doctype aren't; if
+/// one of those is the context node, its parent is used instead.
+fn copy<'d>(from: &nipper::Document, to: dom::Document<'d>, context: nipper::NodeId) -> Copied<'d> {
+    let root = from.root();
+    let mut ids = HashMap::new();
+    ids.insert(to.root().into(), root.id);
+    let mut context_copy = to.root().into();
+
+    /* each node with its copy, which is None for the root */
+    let mut stack: Vec<(nipper::Node, Option<dom::Element>)> = vec![(root, None)];
+
+    while let Some((node, parent)) = stack.pop() {
+        let mut child = node.first_child();
+        while let Some(some) = child {
+            child = some.next_sibling();
+            let id = some.id;
+
+            let copied: nodeset::Node = if some.is_element() {
+                let name = some.node_name().unwrap_or_default();
+                let element = to.create_element(&*name);
+                for attr in some.attrs() {
+                    element.set_attribute_value(&*attr.name.local, &attr.value);
+                }
+                match parent {
+                    Some(parent) => parent.append_child(element),
+                    None => to.root().append_child(element),
+                }
+                stack.push((some, Some(element)));
+                element.into()
+            } else if let (true, Some(parent)) = (some.is_text(), parent) {
+                let text = to.create_text(&some.text());
+                parent.append_child(text);
+                text.into()
+            } else {
+                if id == context {
+                    context_copy = match parent {
+                        Some(parent) => parent.into(),
+                        None => to.root().into(),
+                    };
+                }
+                continue;
+            };
+
+            ids.insert(copied, id);
+            if id == context {
+                context_copy = copied;
+            }
+        }
+    }
+
+    Copied {
+        ids,
+        context: context_copy,
+    }
+}